@@ -43,6 +43,9 @@ typedef struct
 
 	/* has_identity_provider */
 	bool has_identity_provider;
+
+	/* "mechanisms" field includes SCRAM-SHA-1 */
+	bool scramSha1;
 } CreateUserSpec;
 
 typedef struct
@@ -52,6 +55,12 @@ typedef struct
 
 	/* "pwd" field */
 	const char *pwd;
+
+	/* "mechanisms" field was given */
+	bool has_mechanisms;
+
+	/* "mechanisms" field includes SCRAM-SHA-1 */
+	bool scramSha1;
 } UpdateUserSpec;
 
 /* GUC that controls the blocked role prefix list */
@@ -63,4 +72,9 @@ char * ValidateAndObtainUserRole(const bson_value_t *rolesDocument);
 /* Method to verify if username is valid */
 bool IsUserNameInvalid(const char *userName);
 
+/* SCRAM-SHA-1 credentials of native users, see auth/scram_sha1.c */
+void StoreScramSha1Credentials(const char *userName, const char *password);
+void DeleteScramSha1Credentials(const char *userName);
+bool HasScramSha1Credentials(const char *userName);
+
 #endif
@@ -2,12 +2,16 @@
 #include "operators/bson_orderby_operators--0.104-0.sql"
 #include "operators/bson_btree_orderby_operators_family--0.104-0.sql"
 #include "schema/bson_orderby_hash_operator_class--0.104-0.sql"
+#include "schema/scram_sha1_credentials--0.104-0.sql"
 
 #include "udfs/projection/bson_projection--0.104-0.sql"
 #include "udfs/index_mgmt/create_index_background--0.104-0.sql"
 #include "udfs/schema_mgmt/compact--0.104-0.sql"
 
 #include "udfs/schema_mgmt/cursor_support--0.104-0.sql"
+#include "udfs/auth/auth_pwd--0.104-0.sql"
+#include "udfs/auth/auth_scram_secret--0.104-0.sql"
+#include "udfs/auth/auth_scram_sha1--0.104-0.sql"
 
 -- Schedule the index build task
 DO LANGUAGE plpgsql $cmd$
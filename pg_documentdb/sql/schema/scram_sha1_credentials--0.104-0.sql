@@ -0,0 +1,18 @@
+/*
+ * SCRAM-SHA-1 keys of native users created with the SCRAM-SHA-1 mechanism.
+ * Postgres only keeps SCRAM-SHA-256 secrets, so these are stored separately. A row
+ * is only used while the role's SCRAM-SHA-256 salt is still scram_sha256_salt, so
+ * changing the password outside of updateUser invalidates it.
+ */
+CREATE TABLE __API_CATALOG_SCHEMA_V2__.scram_sha1_credentials
+(
+    role_name text not null PRIMARY KEY,
+    iterations integer not null,
+    salt text not null,
+    stored_key text not null,
+    server_key text not null,
+    scram_sha256_salt text not null
+);
+
+-- Only the extension owner reads and writes the keys
+REVOKE ALL ON TABLE __API_CATALOG_SCHEMA_V2__.scram_sha1_credentials FROM PUBLIC;
@@ -0,0 +1,9 @@
+/* Authenticate a user name and plain text password against the password stored in postgresql */
+CREATE OR REPLACE FUNCTION __API_SCHEMA_INTERNAL_V2__.authenticate_with_pwd(
+    p_user_name text, p_password text)
+ RETURNS boolean
+ LANGUAGE C
+PARALLEL SAFE STABLE
+AS 'MODULE_PATHNAME', $$command_authenticate_with_pwd$$;
+COMMENT ON FUNCTION __API_SCHEMA_INTERNAL_V2__.authenticate_with_pwd(text, text)
+    IS 'Used to authenticate the user with Postgresql DB using a plain text password';
@@ -0,0 +1,27 @@
+/*
+ * scram_sha256_get_secret() gets the SCRAM-SHA-256 secret of a user created through
+ * createUser, so that the gateway can cache it and verify client proofs without a
+ * backend round trip. pg_authid is only readable by superusers, so the function runs
+ * as its owner and only returns secrets of non-superuser members of the API roles.
+ */
+CREATE OR REPLACE FUNCTION __API_SCHEMA_INTERNAL_V2__.scram_sha256_get_secret(
+    p_user_name text)
+ RETURNS text
+ LANGUAGE sql
+STABLE STRICT SECURITY DEFINER
+SET search_path = pg_catalog
+AS $fn$
+    SELECT a.rolpassword FROM pg_catalog.pg_authid a
+    WHERE a.rolname = p_user_name AND a.rolcanlogin AND NOT a.rolsuper
+      AND a.rolpassword LIKE 'SCRAM-SHA-256$%'
+      AND (a.rolvaliduntil IS NULL OR a.rolvaliduntil > now())
+      AND EXISTS (
+        SELECT 1 FROM pg_catalog.pg_auth_members am
+        JOIN pg_catalog.pg_roles parent ON parent.oid = am.roleid
+        WHERE am.member = a.oid
+          AND parent.rolname IN (__SINGLE_QUOTED_STRING__(__API_ADMIN_ROLE_V2__), __SINGLE_QUOTED_STRING__(__API_READONLY_ROLE__)));
+$fn$;
+COMMENT ON FUNCTION __API_SCHEMA_INTERNAL_V2__.scram_sha256_get_secret(text)
+    IS 'Gets the SCRAM-SHA-256 secret of the given documentdb user from the backend';
+REVOKE ALL ON FUNCTION __API_SCHEMA_INTERNAL_V2__.scram_sha256_get_secret(text) FROM PUBLIC;
+GRANT EXECUTE ON FUNCTION __API_SCHEMA_INTERNAL_V2__.scram_sha256_get_secret(text) TO __API_ADMIN_ROLE__;
@@ -0,0 +1,22 @@
+/* Authenticate using SCRAM SHA1 against the stored SCRAM-SHA-1 keys of a user */
+CREATE OR REPLACE FUNCTION __API_SCHEMA_INTERNAL_V2__.authenticate_with_scram_sha1(
+    p_user_name text, p_auth_msg text, p_client_proof text)
+ RETURNS __CORE_SCHEMA_V2__.bson
+ LANGUAGE C
+PARALLEL SAFE STABLE
+AS 'MODULE_PATHNAME', $$command_authenticate_with_scram_sha1$$;
+COMMENT ON FUNCTION __API_SCHEMA_INTERNAL_V2__.authenticate_with_scram_sha1(text, text, text)
+    IS 'Used to authenticate the user using SCRAM SHA-1';
+
+/*
+ * scram_sha1_get_salt_and_iterations() gets SALT and Iteration
+ * count of the SCRAM-SHA-1 keys of the given user
+ */
+CREATE OR REPLACE FUNCTION __API_SCHEMA_INTERNAL_V2__.scram_sha1_get_salt_and_iterations(
+    p_user_name text)
+ RETURNS __CORE_SCHEMA_V2__.bson
+ LANGUAGE C
+PARALLEL SAFE STABLE
+AS 'MODULE_PATHNAME', $$command_scram_sha1_get_salt_and_iterations$$;
+COMMENT ON FUNCTION __API_SCHEMA_INTERNAL_V2__.scram_sha1_get_salt_and_iterations(text)
+    IS 'Gets SALT and Iteration count of the SCRAM-SHA-1 keys of the given user';
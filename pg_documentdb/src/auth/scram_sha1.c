@@ -0,0 +1,547 @@
+/*-------------------------------------------------------------------------
+ * Copyright (c) Microsoft Corporation.  All rights reserved.
+ *
+ * src/auth/scram_sha1.c
+ *
+ * Implementation of SCRAM SHA 1 authentication.
+ *
+ * Postgres only keeps SCRAM-SHA-256 secrets, so the SCRAM-SHA-1 keys of
+ * native users are stored in the scram_sha1_credentials catalog table.
+ * As MongoDB does, the keys are derived from the digested password
+ * hex(MD5(user ":mongo:" password)) rather than the password itself.
+ *
+ *-------------------------------------------------------------------------
+ */
+
+#include "postgres.h"
+#include "miscadmin.h"
+#include "catalog/pg_type.h"
+#include "common/base64.h"
+#include "common/cryptohash.h"
+#include "common/hmac.h"
+#include "executor/spi.h"
+#include "libpq/crypt.h"
+#include "port/pg_bswap.h"
+#include "utils/builtins.h"
+#include "io/bson_core.h"
+#include "metadata/metadata_cache.h"
+#include "utils/query_utils.h"
+#include "users.h"
+
+#define SCRAM_SHA1_KEY_LEN 20
+#define SCRAM_SHA1_SALT_LEN 16
+#define SCRAM_SHA1_DEFAULT_ITERATIONS 10000
+#define MD5_DIGEST_LEN 16
+
+#define SCRAM_SHA256_SECRET_PREFIX "SCRAM-SHA-256$"
+
+/*
+ * SCRAM-SHA-1 keys of a native user, as stored in the catalog table.
+ */
+typedef struct ScramSha1Credentials
+{
+	int iterations;
+	char *encodedSalt;
+	uint8 storedKey[SCRAM_SHA1_KEY_LEN];
+	uint8 serverKey[SCRAM_SHA1_KEY_LEN];
+} ScramSha1Credentials;
+
+static bool LookupScramSha1Credentials(const char *userName,
+									   ScramSha1Credentials *credentials);
+static char * GetScramSha256Salt(const char *userName);
+static bool DecodeKey(const char *encoded, uint8 *key);
+static char * EncodeBase64(const uint8 *data, int length);
+static char * DigestPassword(const char *userName, const char *password);
+static bool ScramSha1Hmac(const uint8 *key, int keyLength, const uint8 *data,
+						  int dataLength, uint8 *result);
+static bool ScramSha1Hash(const uint8 *data, int dataLength, uint8 *result);
+static bool ScramSha1SaltedPassword(const char *password, const uint8 *salt,
+									int saltLength, int iterations,
+									uint8 *saltedPassword);
+static pgbson * BuildSaltResponse(bool ok, int iterations, const char *encodedSalt);
+static pgbson * BuildAuthResponse(bool ok, const char *serverSignature);
+
+PG_FUNCTION_INFO_V1(command_authenticate_with_scram_sha1);
+PG_FUNCTION_INFO_V1(command_scram_sha1_get_salt_and_iterations);
+
+
+/*
+ * Provides the encoded salt and iteration count of the SCRAM-SHA-1
+ * credentials of the given user.
+ * Input argument 1: User name. Type: text
+ * Output: { "ok" : 1, "iterations" : int, "salt" : text }
+ */
+Datum
+command_scram_sha1_get_salt_and_iterations(PG_FUNCTION_ARGS)
+{
+	ScramSha1Credentials credentials;
+
+	if (PG_ARGISNULL(0) ||
+		!LookupScramSha1Credentials(text_to_cstring(PG_GETARG_TEXT_P(0)),
+									&credentials))
+	{
+		PG_RETURN_POINTER(BuildSaltResponse(false, 0, ""));
+	}
+
+	PG_RETURN_POINTER(BuildSaltResponse(true, credentials.iterations,
+										credentials.encodedSalt));
+}
+
+
+/*
+ * Verifies a SCRAM-SHA-1 client proof against the stored credentials of
+ * the given user.
+ * Input argument 1: User name. Type: text
+ * Input argument 2: Auth message. Type: text
+ * Input argument 3: Client proof. Type: text
+ * Output: {ok: 1, ServerSignature: text}
+ */
+Datum
+command_authenticate_with_scram_sha1(PG_FUNCTION_ARGS)
+{
+	ScramSha1Credentials credentials;
+	uint8 clientProof[SCRAM_SHA1_KEY_LEN];
+	uint8 clientSignature[SCRAM_SHA1_KEY_LEN];
+	uint8 clientKey[SCRAM_SHA1_KEY_LEN];
+	uint8 clientStoredKey[SCRAM_SHA1_KEY_LEN];
+	uint8 serverSignature[SCRAM_SHA1_KEY_LEN];
+
+	if (PG_ARGISNULL(0) || PG_ARGISNULL(1) || PG_ARGISNULL(2))
+	{
+		PG_RETURN_POINTER(BuildAuthResponse(false, ""));
+	}
+
+	char *userName = text_to_cstring(PG_GETARG_TEXT_P(0));
+	char *authMessage = text_to_cstring(PG_GETARG_TEXT_P(1));
+	char *proof = text_to_cstring(PG_GETARG_TEXT_P(2));
+
+	if (!DecodeKey(proof, clientProof))
+	{
+		ereport(LOG, (errmsg("Malformed SCRAM message.")));
+		PG_RETURN_POINTER(BuildAuthResponse(false, ""));
+	}
+
+	if (!LookupScramSha1Credentials(userName, &credentials))
+	{
+		PG_RETURN_POINTER(BuildAuthResponse(false, ""));
+	}
+
+	/* ClientKey = ClientProof ^ HMAC(StoredKey, AuthMessage) */
+	if (!ScramSha1Hmac(credentials.storedKey, SCRAM_SHA1_KEY_LEN,
+					   (uint8 *) authMessage, strlen(authMessage), clientSignature))
+	{
+		PG_RETURN_POINTER(BuildAuthResponse(false, ""));
+	}
+
+	for (int i = 0; i < SCRAM_SHA1_KEY_LEN; i++)
+	{
+		clientKey[i] = clientProof[i] ^ clientSignature[i];
+	}
+
+	/* H(ClientKey) = StoredKey */
+	if (!ScramSha1Hash(clientKey, SCRAM_SHA1_KEY_LEN, clientStoredKey) ||
+		memcmp(clientStoredKey, credentials.storedKey, SCRAM_SHA1_KEY_LEN) != 0)
+	{
+		ereport(LOG, (errmsg("Client proof verification failed.")));
+		PG_RETURN_POINTER(BuildAuthResponse(false, ""));
+	}
+
+	/* ServerSignature = HMAC(ServerKey, AuthMessage) */
+	if (!ScramSha1Hmac(credentials.serverKey, SCRAM_SHA1_KEY_LEN,
+					   (uint8 *) authMessage, strlen(authMessage), serverSignature))
+	{
+		PG_RETURN_POINTER(BuildAuthResponse(false, ""));
+	}
+
+	PG_RETURN_POINTER(BuildAuthResponse(true,
+										EncodeBase64(serverSignature,
+													 SCRAM_SHA1_KEY_LEN)));
+}
+
+
+/*
+ * StoreScramSha1Credentials derives the SCRAM-SHA-1 keys of a native user
+ * from its password and stores them. They are bound to the role's current
+ * SCRAM-SHA-256 salt, so the role's password must already be set.
+ */
+void
+StoreScramSha1Credentials(const char *userName, const char *password)
+{
+	uint8 salt[SCRAM_SHA1_SALT_LEN];
+	uint8 saltedPassword[SCRAM_SHA1_KEY_LEN];
+	uint8 clientKey[SCRAM_SHA1_KEY_LEN];
+	uint8 storedKey[SCRAM_SHA1_KEY_LEN];
+	uint8 serverKey[SCRAM_SHA1_KEY_LEN];
+
+	char *scramSha256Salt = GetScramSha256Salt(userName);
+	if (scramSha256Salt == NULL)
+	{
+		ereport(ERROR, (errcode(ERRCODE_INTERNAL_ERROR),
+						errmsg("Could not find the SCRAM-SHA-256 secret of the user.")));
+	}
+
+	if (!pg_strong_random(salt, SCRAM_SHA1_SALT_LEN))
+	{
+		ereport(ERROR, (errcode(ERRCODE_INTERNAL_ERROR),
+						errmsg("Could not generate random salt.")));
+	}
+
+	char *digestedPassword = DigestPassword(userName, password);
+	if (digestedPassword == NULL ||
+		!ScramSha1SaltedPassword(digestedPassword, salt, SCRAM_SHA1_SALT_LEN,
+								 SCRAM_SHA1_DEFAULT_ITERATIONS, saltedPassword) ||
+		!ScramSha1Hmac(saltedPassword, SCRAM_SHA1_KEY_LEN, (uint8 *) "Client Key",
+					   strlen("Client Key"), clientKey) ||
+		!ScramSha1Hash(clientKey, SCRAM_SHA1_KEY_LEN, storedKey) ||
+		!ScramSha1Hmac(saltedPassword, SCRAM_SHA1_KEY_LEN, (uint8 *) "Server Key",
+					   strlen("Server Key"), serverKey))
+	{
+		ereport(ERROR, (errcode(ERRCODE_INTERNAL_ERROR),
+						errmsg("Could not derive the SCRAM-SHA-1 keys.")));
+	}
+
+	const char *query = FormatSqlQuery(
+		"INSERT INTO %s.scram_sha1_credentials (role_name, iterations, salt, "
+		"stored_key, server_key, scram_sha256_salt) VALUES ($1, $2, $3, $4, $5, $6) "
+		"ON CONFLICT (role_name) DO UPDATE SET iterations = EXCLUDED.iterations, "
+		"salt = EXCLUDED.salt, stored_key = EXCLUDED.stored_key, "
+		"server_key = EXCLUDED.server_key, scram_sha256_salt = EXCLUDED.scram_sha256_salt",
+		ApiCatalogSchemaNameV2);
+
+	Oid argTypes[6] = { TEXTOID, INT4OID, TEXTOID, TEXTOID, TEXTOID, TEXTOID };
+	Datum argValues[6] = {
+		CStringGetTextDatum(userName),
+		Int32GetDatum(SCRAM_SHA1_DEFAULT_ITERATIONS),
+		CStringGetTextDatum(EncodeBase64(salt, SCRAM_SHA1_SALT_LEN)),
+		CStringGetTextDatum(EncodeBase64(storedKey, SCRAM_SHA1_KEY_LEN)),
+		CStringGetTextDatum(EncodeBase64(serverKey, SCRAM_SHA1_KEY_LEN)),
+		CStringGetTextDatum(scramSha256Salt)
+	};
+
+	/* The table is only accessible to the extension owner */
+	Oid savedUserId = InvalidOid;
+	int savedSecurityContext = 0;
+	GetUserIdAndSecContext(&savedUserId, &savedSecurityContext);
+	SetUserIdAndSecContext(DocumentDBApiExtensionOwner(), SECURITY_LOCAL_USERID_CHANGE);
+
+	bool readOnly = false;
+	ExtensionExecuteCappedStatementWithArgsViaSPI(query, 6, argTypes, argValues, NULL,
+												  readOnly, SPI_OK_INSERT, 0, 0);
+
+	SetUserIdAndSecContext(savedUserId, savedSecurityContext);
+}
+
+
+/*
+ * DeleteScramSha1Credentials removes the SCRAM-SHA-1 credentials of a user, if any.
+ */
+void
+DeleteScramSha1Credentials(const char *userName)
+{
+	const char *query = FormatSqlQuery(
+		"DELETE FROM %s.scram_sha1_credentials WHERE role_name = $1",
+		ApiCatalogSchemaNameV2);
+
+	Oid argTypes[1] = { TEXTOID };
+	Datum argValues[1] = { CStringGetTextDatum(userName) };
+
+	Oid savedUserId = InvalidOid;
+	int savedSecurityContext = 0;
+	GetUserIdAndSecContext(&savedUserId, &savedSecurityContext);
+	SetUserIdAndSecContext(DocumentDBApiExtensionOwner(), SECURITY_LOCAL_USERID_CHANGE);
+
+	bool readOnly = false;
+	ExtensionExecuteCappedStatementWithArgsViaSPI(query, 1, argTypes, argValues, NULL,
+												  readOnly, SPI_OK_DELETE, 0, 0);
+
+	SetUserIdAndSecContext(savedUserId, savedSecurityContext);
+}
+
+
+/*
+ * HasScramSha1Credentials returns whether SCRAM-SHA-1 credentials are stored
+ * for the user, whether or not they are still valid.
+ */
+bool
+HasScramSha1Credentials(const char *userName)
+{
+	const char *query = FormatSqlQuery(
+		"SELECT EXISTS (SELECT 1 FROM %s.scram_sha1_credentials WHERE role_name = $1)",
+		ApiCatalogSchemaNameV2);
+
+	Oid argTypes[1] = { TEXTOID };
+	Datum argValues[1] = { CStringGetTextDatum(userName) };
+
+	Oid savedUserId = InvalidOid;
+	int savedSecurityContext = 0;
+	GetUserIdAndSecContext(&savedUserId, &savedSecurityContext);
+	SetUserIdAndSecContext(DocumentDBApiExtensionOwner(), SECURITY_LOCAL_USERID_CHANGE);
+
+	bool readOnly = true;
+	bool isNull = true;
+	Datum result = ExtensionExecuteQueryWithArgsViaSPI(query, 1, argTypes, argValues,
+													   NULL, readOnly, SPI_OK_SELECT,
+													   &isNull);
+
+	SetUserIdAndSecContext(savedUserId, savedSecurityContext);
+
+	return !isNull && DatumGetBool(result);
+}
+
+
+/*
+ * Reads the SCRAM-SHA-1 credentials of the user. They are only valid while
+ * the role can log in with the password they were derived from, which is
+ * checked by comparing the SCRAM-SHA-256 salt they were stored with.
+ */
+static bool
+LookupScramSha1Credentials(const char *userName, ScramSha1Credentials *credentials)
+{
+	const char *query = FormatSqlQuery(
+		"SELECT iterations, salt, stored_key, server_key, scram_sha256_salt "
+		"FROM %s.scram_sha1_credentials WHERE role_name = $1",
+		ApiCatalogSchemaNameV2);
+
+	Oid argTypes[1] = { TEXTOID };
+	Datum argValues[1] = { CStringGetTextDatum(userName) };
+	Datum results[5];
+	bool isNull[5];
+
+	Oid savedUserId = InvalidOid;
+	int savedSecurityContext = 0;
+	GetUserIdAndSecContext(&savedUserId, &savedSecurityContext);
+	SetUserIdAndSecContext(DocumentDBApiExtensionOwner(), SECURITY_LOCAL_USERID_CHANGE);
+
+	bool readOnly = true;
+	ExtensionExecuteMultiValueQueryWithArgsViaSPI(query, 1, argTypes, argValues, NULL,
+												  readOnly, SPI_OK_SELECT, results,
+												  isNull, 5);
+
+	SetUserIdAndSecContext(savedUserId, savedSecurityContext);
+
+	for (int i = 0; i < 5; i++)
+	{
+		if (isNull[i])
+		{
+			return false;
+		}
+	}
+
+	char *scramSha256Salt = GetScramSha256Salt(userName);
+	if (scramSha256Salt == NULL ||
+		strcmp(scramSha256Salt, TextDatumGetCString(results[4])) != 0)
+	{
+		ereport(LOG, (errmsg("SCRAM-SHA-1 credentials of user [%s] are out of date.",
+							 userName)));
+		return false;
+	}
+
+	credentials->iterations = DatumGetInt32(results[0]);
+	credentials->encodedSalt = TextDatumGetCString(results[1]);
+	return DecodeKey(TextDatumGetCString(results[2]), credentials->storedKey) &&
+		   DecodeKey(TextDatumGetCString(results[3]), credentials->serverKey);
+}
+
+
+/*
+ * Returns the salt of the role's SCRAM-SHA-256 secret, or NULL when the role
+ * cannot log in with a SCRAM-SHA-256 password.
+ * The secret has the form SCRAM-SHA-256$<iterations>:<salt>$<StoredKey>:<ServerKey>
+ */
+static char *
+GetScramSha256Salt(const char *userName)
+{
+#if (PG_VERSION_NUM >= 150000)
+	const char *logDetail = NULL;
+#else
+	char *logDetail = NULL;
+#endif
+
+	char *shadowPass = get_role_password(userName, &logDetail);
+	if (shadowPass == NULL ||
+		strncmp(shadowPass, SCRAM_SHA256_SECRET_PREFIX,
+				strlen(SCRAM_SHA256_SECRET_PREFIX)) != 0)
+	{
+		return NULL;
+	}
+
+	char *salt = strchr(shadowPass + strlen(SCRAM_SHA256_SECRET_PREFIX), ':');
+	char *saltEnd = salt != NULL ? strchr(salt, '$') : NULL;
+	if (saltEnd == NULL)
+	{
+		return NULL;
+	}
+
+	return pnstrdup(salt + 1, saltEnd - salt - 1);
+}
+
+
+/* Decodes a base64 SCRAM-SHA-1 key, which must be exactly the key length */
+static bool
+DecodeKey(const char *encoded, uint8 *key)
+{
+	int decodedLength = pg_b64_dec_len(strlen(encoded));
+	char *decoded = palloc(decodedLength);
+
+	if (pg_b64_decode(encoded, strlen(encoded), decoded, decodedLength) !=
+		SCRAM_SHA1_KEY_LEN)
+	{
+		pfree(decoded);
+		return false;
+	}
+
+	memcpy(key, decoded, SCRAM_SHA1_KEY_LEN);
+	pfree(decoded);
+	return true;
+}
+
+
+static char *
+EncodeBase64(const uint8 *data, int length)
+{
+	int encodedLength = pg_b64_enc_len(length);
+	char *encoded = palloc0(encodedLength + 1);
+
+	encodedLength = pg_b64_encode((const char *) data, length, encoded, encodedLength);
+	encoded[encodedLength < 0 ? 0 : encodedLength] = '\0';
+	return encoded;
+}
+
+
+/* Returns hex(MD5(user ":mongo:" password)), the password SCRAM-SHA-1 keys are derived from */
+static char *
+DigestPassword(const char *userName, const char *password)
+{
+	uint8 digest[MD5_DIGEST_LEN];
+	char *input = psprintf("%s:mongo:%s", userName, password);
+	pg_cryptohash_ctx *ctx = pg_cryptohash_create(PG_MD5);
+
+	if (pg_cryptohash_init(ctx) < 0 ||
+		pg_cryptohash_update(ctx, (uint8 *) input, strlen(input)) < 0 ||
+		pg_cryptohash_final(ctx, digest, sizeof(digest)) < 0)
+	{
+		pg_cryptohash_free(ctx);
+		return NULL;
+	}
+
+	pg_cryptohash_free(ctx);
+
+	char *hex = palloc0(MD5_DIGEST_LEN * 2 + 1);
+	for (int i = 0; i < MD5_DIGEST_LEN; i++)
+	{
+		sprintf(hex + i * 2, "%02x", digest[i]);
+	}
+
+	return hex;
+}
+
+
+static bool
+ScramSha1Hmac(const uint8 *key, int keyLength, const uint8 *data, int dataLength,
+			  uint8 *result)
+{
+	pg_hmac_ctx *ctx = pg_hmac_create(PG_SHA1);
+
+	if (pg_hmac_init(ctx, key, keyLength) < 0 ||
+		pg_hmac_update(ctx, data, dataLength) < 0 ||
+		pg_hmac_final(ctx, result, SCRAM_SHA1_KEY_LEN) < 0)
+	{
+		pg_hmac_free(ctx);
+		return false;
+	}
+
+	pg_hmac_free(ctx);
+	return true;
+}
+
+
+static bool
+ScramSha1Hash(const uint8 *data, int dataLength, uint8 *result)
+{
+	pg_cryptohash_ctx *ctx = pg_cryptohash_create(PG_SHA1);
+
+	if (pg_cryptohash_init(ctx) < 0 ||
+		pg_cryptohash_update(ctx, data, dataLength) < 0 ||
+		pg_cryptohash_final(ctx, result, SCRAM_SHA1_KEY_LEN) < 0)
+	{
+		pg_cryptohash_free(ctx);
+		return false;
+	}
+
+	pg_cryptohash_free(ctx);
+	return true;
+}
+
+
+/*
+ * SaltedPassword = Hi(password, salt, i), the PBKDF2 of the password with
+ * HMAC-SHA-1 as its pseudorandom function.
+ */
+static bool
+ScramSha1SaltedPassword(const char *password, const uint8 *salt, int saltLength,
+						int iterations, uint8 *saltedPassword)
+{
+	uint32 one = pg_hton32(1);
+	uint8 block[SCRAM_SHA1_KEY_LEN];
+	int passwordLength = strlen(password);
+	pg_hmac_ctx *ctx = pg_hmac_create(PG_SHA1);
+
+	/* U1 = HMAC(password, salt + INT(1)) */
+	if (pg_hmac_init(ctx, (uint8 *) password, passwordLength) < 0 ||
+		pg_hmac_update(ctx, salt, saltLength) < 0 ||
+		pg_hmac_update(ctx, (uint8 *) &one, sizeof(one)) < 0 ||
+		pg_hmac_final(ctx, block, sizeof(block)) < 0)
+	{
+		pg_hmac_free(ctx);
+		return false;
+	}
+
+	memcpy(saltedPassword, block, SCRAM_SHA1_KEY_LEN);
+
+	/* Ui = HMAC(password, Ui-1), and the result is U1 ^ U2 ^ ... ^ Ui */
+	for (int i = 2; i <= iterations; i++)
+	{
+		if (pg_hmac_init(ctx, (uint8 *) password, passwordLength) < 0 ||
+			pg_hmac_update(ctx, block, sizeof(block)) < 0 ||
+			pg_hmac_final(ctx, block, sizeof(block)) < 0)
+		{
+			pg_hmac_free(ctx);
+			return false;
+		}
+
+		for (int j = 0; j < SCRAM_SHA1_KEY_LEN; j++)
+		{
+			saltedPassword[j] ^= block[j];
+		}
+	}
+
+	pg_hmac_free(ctx);
+	return true;
+}
+
+
+static pgbson *
+BuildSaltResponse(bool ok, int iterations, const char *encodedSalt)
+{
+	pgbson_writer resultWriter;
+
+	PgbsonWriterInit(&resultWriter);
+	PgbsonWriterAppendInt32(&resultWriter, "ok", 2, ok ? 1 : 0);
+	PgbsonWriterAppendInt32(&resultWriter, "iterations", 10, iterations);
+	PgbsonWriterAppendUtf8(&resultWriter, "salt", 4, encodedSalt);
+
+	return PgbsonWriterGetPgbson(&resultWriter);
+}
+
+
+static pgbson *
+BuildAuthResponse(bool ok, const char *serverSignature)
+{
+	pgbson_writer resultWriter;
+
+	PgbsonWriterInit(&resultWriter);
+	PgbsonWriterAppendInt32(&resultWriter, "ok", 2, ok ? 1 : 0);
+	PgbsonWriterAppendUtf8(&resultWriter, "ServerSignature", 15, serverSignature);
+
+	return PgbsonWriterGetPgbson(&resultWriter);
+}
@@ -52,6 +52,7 @@ static UpdateUserSpec * ParseUpdateUserSpec(pgbson *updateSpec);
 static Datum UpdateNativeUser(UpdateUserSpec *spec);
 static char * ParseGetUserSpec(pgbson *getSpec);
 static char * PrehashPassword(const char *password);
+static bool ParseMechanisms(const bson_value_t *mechanisms);
 static bool IsCallingUserExternal(void);
 static bool IsPasswordInvalid(const char *username, const char *password);
 
@@ -176,6 +177,11 @@ documentdb_extension_create_user(PG_FUNCTION_ARGS)
 		isNull = false;
 		ExtensionExecuteQueryViaSPI(createUserInfo->data, readOnly, SPI_OK_UTILITY,
 									&isNull);
+
+		if (createUserSpec->scramSha1)
+		{
+			StoreScramSha1Credentials(createUserSpec->createUser, createUserSpec->pwd);
+		}
 	}
 
 	/* Grant pgRole to user created */
@@ -277,6 +283,10 @@ ParseCreateUserSpec(pgbson *createSpec)
 			spec->pgRole = ValidateAndObtainUserRole(&spec->roles);
 			has_roles = true;
 		}
+		else if (strcmp(key, "mechanisms") == 0)
+		{
+			spec->scramSha1 = ParseMechanisms(bson_iter_value(&createIter));
+		}
 		else if (strcmp(key, "customData") == 0)
 		{
 			const bson_value_t *customDataDocument = bson_iter_value(&createIter);
@@ -331,6 +341,12 @@ ParseCreateUserSpec(pgbson *createSpec)
 			ereport(ERROR, (errcode(ERRCODE_DOCUMENTDB_BADVALUE), errmsg(
 								"Password is not allowed when using an external identity provider.")));
 		}
+
+		if (spec->scramSha1)
+		{
+			ereport(ERROR, (errcode(ERRCODE_DOCUMENTDB_BADVALUE), errmsg(
+								"SCRAM-SHA-1 is not allowed when using an external identity provider.")));
+		}
 	}
 	else
 	{
@@ -540,6 +556,8 @@ documentdb_extension_drop_user(PG_FUNCTION_ARGS)
 		bool isNull = false;
 		ExtensionExecuteQueryViaSPI(dropUserInfo->data, readOnly, SPI_OK_UTILITY,
 									&isNull);
+
+		DeleteScramSha1Credentials(dropUser);
 	}
 
 	pgbson_writer finalWriter;
@@ -708,6 +726,11 @@ ParseUpdateUserSpec(pgbson *updateSpec)
 			uint32_t strLength = 0;
 			spec->pwd = bson_iter_utf8(&updateIter, &strLength);
 		}
+		else if (strcmp(key, "mechanisms") == 0)
+		{
+			spec->scramSha1 = ParseMechanisms(bson_iter_value(&updateIter));
+			spec->has_mechanisms = true;
+		}
 		else if (strcmp(key, "lsid") == 0 || strcmp(key, "$db") == 0)
 		{
 			continue;
@@ -772,6 +795,18 @@ UpdateNativeUser(UpdateUserSpec *spec)
 	ExtensionExecuteQueryViaSPI(updateUserInfo->data, readOnly, SPI_OK_UTILITY,
 								&isNull);
 
+	/* Without mechanisms the user keeps SCRAM-SHA-1 if they had it, with keys for the new password */
+	bool scramSha1 = spec->has_mechanisms ? spec->scramSha1 :
+					 HasScramSha1Credentials(spec->updateUser);
+	if (scramSha1)
+	{
+		StoreScramSha1Credentials(spec->updateUser, spec->pwd);
+	}
+	else
+	{
+		DeleteScramSha1Credentials(spec->updateUser);
+	}
+
 	pgbson_writer finalWriter;
 	PgbsonWriterInit(&finalWriter);
 	PgbsonWriterAppendInt32(&finalWriter, "ok", 2, 1);
@@ -1064,6 +1099,53 @@ ParseGetUserSpec(pgbson *getSpec)
 }
 
 
+/*
+ * ParseMechanisms validates the "mechanisms" field of createUser and updateUser
+ * and returns whether it includes SCRAM-SHA-1. Postgres logins need the
+ * SCRAM-SHA-256 secret, so SCRAM-SHA-256 must always be included.
+ */
+static bool
+ParseMechanisms(const bson_value_t *mechanisms)
+{
+	if (mechanisms->value_type != BSON_TYPE_ARRAY)
+	{
+		ereport(ERROR, (errcode(ERRCODE_DOCUMENTDB_TYPEMISMATCH),
+						errmsg("'mechanisms' must be an array.")));
+	}
+
+	bool scramSha1 = false;
+	bool scramSha256 = false;
+	bson_iter_t mechanismsIter;
+	BsonValueInitIterator(mechanisms, &mechanismsIter);
+	while (bson_iter_next(&mechanismsIter))
+	{
+		const char *mechanism = BSON_ITER_HOLDS_UTF8(&mechanismsIter) ?
+								bson_iter_utf8(&mechanismsIter, NULL) : "";
+		if (strcmp(mechanism, "SCRAM-SHA-1") == 0)
+		{
+			scramSha1 = true;
+		}
+		else if (strcmp(mechanism, "SCRAM-SHA-256") == 0)
+		{
+			scramSha256 = true;
+		}
+		else
+		{
+			ereport(ERROR, (errcode(ERRCODE_DOCUMENTDB_BADVALUE),
+							errmsg("Unsupported mechanism specified in 'mechanisms'.")));
+		}
+	}
+
+	if (!scramSha256)
+	{
+		ereport(ERROR, (errcode(ERRCODE_DOCUMENTDB_BADVALUE),
+						errmsg("'mechanisms' must include SCRAM-SHA-256.")));
+	}
+
+	return scramSha1;
+}
+
+
 /*
  * This method is mostly copied from pg_be_scram_build_secret in PG. The only substantial change
  * is that we use a default salt length of 28 as opposed to 16 used by PG. This is to ensure
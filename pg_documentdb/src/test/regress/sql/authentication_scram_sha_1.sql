@@ -0,0 +1,82 @@
+SET search_path TO documentdb_api_catalog;
+SET documentdb.maxUserLimit TO 10;
+\set VERBOSITY TERSE
+
+-- TEST SCRAM SHA-1 authentication support functions from extension
+--   Test file to test the functions:
+--     1. documentdb_api_internal.scram_sha1_get_salt_and_iterations() and
+--     2. documentdb_api_internal.authenticate_with_scram_sha1()
+SET client_min_messages TO ERROR;
+CREATE ROLE sha1_user WITH LOGIN PASSWORD 'SCRAM-SHA-256$4096:MDEyMzQ1Njc4OWFiY2RlZg==$DoL+erKgUD9rzbJvecoDl4hGv+imWphJxVs9xAJ5Ojw=:M2o9tldkuj/cqyXmb56M+EtJvrFexFogFHglj0uqCA8=';
+CREATE ROLE sha256_user WITH LOGIN PASSWORD '<password_placeholder1>';
+RESET client_min_messages;
+
+-- Keys derived from hex(MD5('sha1_user:mongo:sha1_test_password')) with the salt 'fedcba9876543210' and 10000 iterations,
+-- bound to the SCRAM-SHA-256 salt of the role
+INSERT INTO scram_sha1_credentials VALUES ('sha1_user', 10000, 'ZmVkY2JhOTg3NjU0MzIxMA==', '+IZxZEAWBdtIhqFqZDXdPo1Hcn0=', 'Zq6XYiXOlgdFeJ52HNcnXD1fOJM=', 'MDEyMzQ1Njc4OWFiY2RlZg==');
+
+/* negative test cases for scram_sha1_get_salt_and_iterations */
+-- 1. null user name
+SELECT documentdb_api_internal.scram_sha1_get_salt_and_iterations(null);
+
+-- 2. non existent user name
+SELECT documentdb_api_internal.scram_sha1_get_salt_and_iterations('nonexistent');
+
+-- 3. user without SCRAM-SHA-1 keys
+SELECT documentdb_api_internal.scram_sha1_get_salt_and_iterations('sha256_user');
+
+-- Salt and iterations of the stored keys
+SELECT documentdb_api_internal.scram_sha1_get_salt_and_iterations('sha1_user');
+
+/* authenticate_with_scram_sha1 */
+-- 1. valid client proof, returns the server signature
+SELECT documentdb_api_internal.authenticate_with_scram_sha1('sha1_user', 'n=sha1_user,r=clientnonce,r=clientnonceservernonce,s=ZmVkY2JhOTg3NjU0MzIxMA==,i=10000,c=biws,r=clientnonceservernonce', 'SYBtOU6B9pmdfe8lRlaa1SKHaYY=');
+
+-- 2. incorrect client proof
+SELECT documentdb_api_internal.authenticate_with_scram_sha1('sha1_user', 'n=sha1_user,r=clientnonce,r=clientnonceservernonce,s=ZmVkY2JhOTg3NjU0MzIxMA==,i=10000,c=biws,r=clientnonceservernonce', 'SIBtOU6B9pmdfe8lRlaa1SKHaYY=');
+
+-- 3. malformed client proof
+SELECT documentdb_api_internal.authenticate_with_scram_sha1('sha1_user', 'authMsg1', 'clientProof123');
+
+-- 4. null user name
+SELECT documentdb_api_internal.authenticate_with_scram_sha1(null, 'authMsg1', 'SYBtOU6B9pmdfe8lRlaa1SKHaYY=');
+
+-- 5. user without SCRAM-SHA-1 keys
+SELECT documentdb_api_internal.authenticate_with_scram_sha1('sha256_user', 'authMsg1', 'SYBtOU6B9pmdfe8lRlaa1SKHaYY=');
+
+-- Changing the password outside of updateUser invalidates the keys
+SET client_min_messages TO ERROR;
+ALTER ROLE sha1_user WITH PASSWORD '<password_placeholder2>';
+RESET client_min_messages;
+SELECT documentdb_api_internal.scram_sha1_get_salt_and_iterations('sha1_user');
+SELECT documentdb_api_internal.authenticate_with_scram_sha1('sha1_user', 'n=sha1_user,r=clientnonce,r=clientnonceservernonce,s=ZmVkY2JhOTg3NjU0MzIxMA==,i=10000,c=biws,r=clientnonceservernonce', 'SYBtOU6B9pmdfe8lRlaa1SKHaYY=');
+
+DELETE FROM scram_sha1_credentials WHERE role_name = 'sha1_user';
+DROP ROLE sha1_user;
+DROP ROLE sha256_user;
+
+/* createUser, updateUser and dropUser keep the SCRAM-SHA-1 keys in sync */
+-- Users get SCRAM-SHA-1 keys only when asked for
+SELECT documentdb_api.create_user('{"createUser":"sha1_crud_user", "pwd":"test_password", "roles":[{"role":"readAnyDatabase","db":"admin"}], "mechanisms":["SCRAM-SHA-1", "SCRAM-SHA-256"]}');
+SELECT documentdb_api.create_user('{"createUser":"sha256_crud_user", "pwd":"test_password", "roles":[{"role":"readAnyDatabase","db":"admin"}]}');
+SELECT role_name, iterations, length(salt) FROM scram_sha1_credentials ORDER BY role_name;
+SELECT documentdb_api_internal.scram_sha1_get_salt_and_iterations('sha1_crud_user')::text LIKE '{ "ok" : { "$numberInt" : "1" }, "iterations" : { "$numberInt" : "10000" }, %';
+
+-- A new password keeps SCRAM-SHA-1, with keys for the new password
+SELECT documentdb_api.update_user('{"updateUser":"sha1_crud_user", "pwd":"new_password"}');
+SELECT documentdb_api_internal.scram_sha1_get_salt_and_iterations('sha1_crud_user')::text LIKE '{ "ok" : { "$numberInt" : "1" }, "iterations" : { "$numberInt" : "10000" }, %';
+
+-- mechanisms add and remove SCRAM-SHA-1
+SELECT documentdb_api.update_user('{"updateUser":"sha1_crud_user", "pwd":"new_password", "mechanisms":["SCRAM-SHA-256"]}');
+SELECT documentdb_api.update_user('{"updateUser":"sha256_crud_user", "pwd":"new_password", "mechanisms":["SCRAM-SHA-256", "SCRAM-SHA-1"]}');
+SELECT role_name FROM scram_sha1_credentials ORDER BY role_name;
+
+-- Dropping the user removes its keys
+SELECT documentdb_api.drop_user('{"dropUser":"sha256_crud_user"}');
+SELECT documentdb_api.drop_user('{"dropUser":"sha1_crud_user"}');
+SELECT COUNT(*) FROM scram_sha1_credentials;
+
+-- Invalid mechanisms
+SELECT documentdb_api.create_user('{"createUser":"sha1_crud_user", "pwd":"test_password", "roles":[{"role":"readAnyDatabase","db":"admin"}], "mechanisms":["SCRAM-SHA-1"]}');
+SELECT documentdb_api.create_user('{"createUser":"sha1_crud_user", "pwd":"test_password", "roles":[{"role":"readAnyDatabase","db":"admin"}], "mechanisms":["SCRAM-SHA-256", "MONGODB-X509"]}');
+SELECT documentdb_api.create_user('{"createUser":"sha1_crud_user", "pwd":"test_password", "roles":[{"role":"readAnyDatabase","db":"admin"}], "mechanisms":"SCRAM-SHA-1"}');
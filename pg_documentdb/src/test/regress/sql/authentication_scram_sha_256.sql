@@ -181,6 +181,14 @@ SELECT test_documentdb_scram_sha256_dual_api('test\user', '<password_placeholder
 -- Failed test for incorrect password
 SELECT test_documentdb_scram_sha256_dual_api('test\user', '<password_placeholder111>');
 
+-- scram_sha256_get_secret only returns the secrets of non-superuser documentdb users
+SELECT documentdb_api_internal.scram_sha256_get_secret('testuser') IS NULL;
+GRANT documentdb_readonly_role TO testuser;
+SELECT documentdb_api_internal.scram_sha256_get_secret('testuser') LIKE 'SCRAM-SHA-256$%';
+ALTER ROLE testuser SUPERUSER;
+SELECT documentdb_api_internal.scram_sha256_get_secret('testuser') IS NULL;
+ALTER ROLE testuser NOSUPERUSER;
+
 -- DROP THE USERS CREATED FOR THE TEST
 DROP role "testuser";
 DROP role "test""user";
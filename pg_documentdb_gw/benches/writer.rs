@@ -0,0 +1,172 @@
+/*-------------------------------------------------------------------------
+ * Copyright (c) Microsoft Corporation.  All rights reserved.
+ *
+ * benches/writer.rs
+ *
+ *-------------------------------------------------------------------------
+ */
+
+// Measures the cost of writing small OP_MSG replies over TLS, where every write to the stream
+// becomes its own TLS record. `per_field` reproduces the previous writer, which issued a write
+// for each header field and section, as a reference point for `write_message`.
+
+use std::time::Instant;
+
+use bson::{rawdoc, RawDocument, RawDocumentBuf};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use documentdb_gateway::{
+    protocol::{header::Header, opcode::OpCode},
+    responses::writer,
+};
+use openssl::ssl::{Ssl, SslAcceptor, SslConnector, SslMethod, SslVerifyMode};
+use rcgen::{generate_simple_self_signed, CertifiedKey};
+use tokio::{
+    io::{AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream},
+    runtime::Runtime,
+};
+use tokio_openssl::SslStream;
+
+const REPLIES_PER_ITERATION: u64 = 100;
+
+fn header() -> Header {
+    Header {
+        length: 0,
+        request_id: 1,
+        response_to: 0,
+        op_code: OpCode::Msg,
+        activity_id: String::new(),
+        compressor: None,
+        response_checksum: false,
+        response_document_sequences: false,
+    }
+}
+
+fn small_document(documents: i32) -> RawDocumentBuf {
+    let mut batch = bson::RawArrayBuf::new();
+    for i in 0..documents {
+        batch.push(rawdoc! { "_id": i, "a": "value" });
+    }
+    rawdoc! {
+        "cursor": { "firstBatch": batch, "id": 0_i64, "ns": "db.coll" },
+        "ok": 1.0,
+    }
+}
+
+/// Connects a TLS server stream to a client whose reads are drained in the background.
+async fn tls_stream() -> SslStream<DuplexStream> {
+    let CertifiedKey { cert, key_pair } =
+        generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor
+        .set_certificate(&openssl::x509::X509::from_pem(cert.pem().as_bytes()).unwrap())
+        .unwrap();
+    acceptor
+        .set_private_key(
+            &openssl::pkey::PKey::private_key_from_pem(key_pair.serialize_pem().as_bytes())
+                .unwrap(),
+        )
+        .unwrap();
+    let acceptor = acceptor.build();
+
+    let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+    connector.set_verify(SslVerifyMode::NONE);
+    let connector = connector.build();
+
+    let (server, client) = tokio::io::duplex(1024 * 1024);
+    let mut server = SslStream::new(Ssl::new(acceptor.context()).unwrap(), server).unwrap();
+    let mut client = SslStream::new(
+        connector
+            .configure()
+            .unwrap()
+            .into_ssl("localhost")
+            .unwrap(),
+        client,
+    )
+    .unwrap();
+
+    let (accepted, connected) = tokio::join!(
+        std::pin::Pin::new(&mut server).accept(),
+        std::pin::Pin::new(&mut client).connect()
+    );
+    accepted.unwrap();
+    connected.unwrap();
+
+    tokio::spawn(async move {
+        let mut buf = vec![0_u8; 64 * 1024];
+        while client.read(&mut buf).await.unwrap_or(0) > 0 {}
+    });
+    server
+}
+
+/// The previous writer: one write per header field and per section part.
+async fn per_field<W: AsyncWrite + Unpin>(header: &Header, response: &RawDocument, stream: &mut W) {
+    let length = (Header::LENGTH + 5 + response.as_bytes().len()) as i32;
+    stream.write_all(&length.to_le_bytes()).await.unwrap();
+    stream
+        .write_all(&header.request_id.to_le_bytes())
+        .await
+        .unwrap();
+    stream
+        .write_all(&header.request_id.to_le_bytes())
+        .await
+        .unwrap();
+    stream
+        .write_all(&(OpCode::Msg as i32).to_le_bytes())
+        .await
+        .unwrap();
+    stream.write_all(&0_u32.to_le_bytes()).await.unwrap();
+    stream.write_all(&[0]).await.unwrap();
+    stream.write_all(response.as_bytes()).await.unwrap();
+    stream.flush().await.unwrap();
+}
+
+fn write_small_replies(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("write_small_replies");
+    group.throughput(Throughput::Elements(REPLIES_PER_ITERATION));
+
+    for documents in [1, 10] {
+        let response = small_document(documents);
+        let header = header();
+
+        group.bench_with_input(
+            BenchmarkId::new("write_message", documents),
+            &response,
+            |b, response| {
+                let mut stream = runtime.block_on(tls_stream());
+                b.iter_custom(|iters| {
+                    runtime.block_on(async {
+                        let start = Instant::now();
+                        for _ in 0..iters * REPLIES_PER_ITERATION {
+                            writer::write_response(&header, response, &mut stream)
+                                .await
+                                .unwrap();
+                        }
+                        start.elapsed()
+                    })
+                });
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("per_field", documents),
+            &response,
+            |b, response| {
+                let mut stream = runtime.block_on(tls_stream());
+                b.iter_custom(|iters| {
+                    runtime.block_on(async {
+                        let start = Instant::now();
+                        for _ in 0..iters * REPLIES_PER_ITERATION {
+                            per_field(&header, response, &mut stream).await;
+                        }
+                        start.elapsed()
+                    })
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, write_small_replies);
+criterion_main!(benches);
@@ -0,0 +1,25 @@
+/*-------------------------------------------------------------------------
+ * Copyright (c) Microsoft Corporation.  All rights reserved.
+ *
+ * fuzz/fuzz_targets/parse_insert.rs
+ *
+ *-------------------------------------------------------------------------
+ */
+
+#![no_main]
+
+use documentdb_gateway::{
+    protocol::{opcode::OpCode, reader::parse_insert},
+    requests::RequestMessage,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let message = RequestMessage {
+        request: data.to_vec(),
+        op_code: OpCode::Insert,
+        request_id: 1,
+        response_to: 0,
+    };
+    let _ = futures::executor::block_on(parse_insert(&message));
+});
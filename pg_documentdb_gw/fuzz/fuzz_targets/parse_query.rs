@@ -0,0 +1,16 @@
+/*-------------------------------------------------------------------------
+ * Copyright (c) Microsoft Corporation.  All rights reserved.
+ *
+ * fuzz/fuzz_targets/parse_query.rs
+ *
+ *-------------------------------------------------------------------------
+ */
+
+#![no_main]
+
+use documentdb_gateway::protocol::reader::parse_query;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = futures::executor::block_on(parse_query(data));
+});
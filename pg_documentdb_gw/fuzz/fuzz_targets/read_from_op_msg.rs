@@ -0,0 +1,18 @@
+/*-------------------------------------------------------------------------
+ * Copyright (c) Microsoft Corporation.  All rights reserved.
+ *
+ * fuzz/fuzz_targets/read_from_op_msg.rs
+ *
+ *-------------------------------------------------------------------------
+ */
+
+#![no_main]
+
+use std::io::Cursor;
+
+use documentdb_gateway::protocol::message::Message;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Message::read_from_op_msg(Cursor::new(data), 1, 0);
+});
@@ -6,16 +6,19 @@
  *-------------------------------------------------------------------------
  */
 
-use std::str::from_utf8;
+use std::{str::from_utf8, sync::Arc, time::SystemTime};
 
-use bson::{rawdoc, spec::BinarySubtype};
+use bson::{rawdoc, spec::BinarySubtype, RawDocument, RawDocumentBuf};
+use openssl::x509::X509NameRef;
 use rand::{distributions::Uniform, prelude::Distribution, rngs::OsRng};
 use tokio_postgres::types::Type;
 
 use crate::{
-    context::ConnectionContext,
+    configuration::OidcOptions,
+    context::{AuthThrottleKey, ConnectionContext, ScramKeys},
     error::{DocumentDBError, ErrorCode, Result},
-    postgres::PgDocument,
+    oidc,
+    postgres::{PgDocument, QueryCatalog},
     processor,
     protocol::OK_SUCCEEDED,
     requests::{Request, RequestInfo, RequestType},
@@ -23,8 +26,52 @@ use crate::{
 };
 
 const NONCE_LENGTH: usize = 2;
+const EXTERNAL_DATABASE: &str = "$external";
+const PLAIN_MECHANISM: &str = "PLAIN";
+const OIDC_MECHANISM: &str = "MONGODB-OIDC";
+
+/// The SCRAM variants accepted by saslStart, each verified by its own backend functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScramMechanism {
+    Sha1,
+    Sha256,
+}
+
+impl ScramMechanism {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "SCRAM-SHA-1" => Some(ScramMechanism::Sha1),
+            "SCRAM-SHA-256" => Some(ScramMechanism::Sha256),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScramMechanism::Sha1 => "SCRAM-SHA-1",
+            ScramMechanism::Sha256 => "SCRAM-SHA-256",
+        }
+    }
+
+    fn salt_and_iterations_query<'a>(&self, query_catalog: &'a QueryCatalog) -> &'a str {
+        match self {
+            ScramMechanism::Sha1 => query_catalog.scram_sha1_salt_and_iterations(),
+            ScramMechanism::Sha256 => query_catalog.salt_and_iterations(),
+        }
+    }
+
+    fn authenticate_query<'a>(&self, query_catalog: &'a QueryCatalog) -> &'a str {
+        match self {
+            ScramMechanism::Sha1 => query_catalog.authenticate_with_scram_sha1(),
+            ScramMechanism::Sha256 => query_catalog.authenticate_with_scram_sha256(),
+        }
+    }
+}
 
 pub struct ScramFirstState {
+    mechanism: ScramMechanism,
+    // Cached keys let the proof be verified without the backend
+    keys: Option<Arc<ScramKeys>>,
     nonce: String,
     first_message_bare: String,
     first_message: String,
@@ -33,8 +80,11 @@ pub struct ScramFirstState {
 pub struct AuthState {
     pub authorized: bool,
     first_state: Option<ScramFirstState>,
+    oidc_started: bool,
     username: Option<String>,
     pub password: Option<String>,
+    // Set for bearer tokens, the connection must authenticate again once the token expires
+    expires_at: Option<SystemTime>,
 }
 
 impl Default for AuthState {
@@ -48,11 +98,18 @@ impl AuthState {
         AuthState {
             authorized: false,
             first_state: None,
+            oidc_started: false,
             username: None,
             password: None,
+            expires_at: None,
         }
     }
 
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= SystemTime::now())
+    }
+
     pub fn username(&self) -> Result<&str> {
         self.username
             .as_deref()
@@ -66,6 +123,18 @@ impl AuthState {
     }
 }
 
+/// Fails once the credential the connection authenticated with has expired, which tells drivers to authenticate again.
+pub fn check_expiry(ctx: &mut ConnectionContext) -> Result<()> {
+    if ctx.auth_state.authorized && ctx.auth_state.is_expired() {
+        ctx.auth_state = AuthState::new();
+        return Err(DocumentDBError::documentdb_error(
+            ErrorCode::ReauthenticationRequired,
+            "Access token has expired, reauthentication is required".to_string(),
+        ));
+    }
+    Ok(())
+}
+
 pub async fn process(ctx: &mut ConnectionContext, request: &Request<'_>) -> Result<Response> {
     if let Some(response) = handle_auth_request(ctx, request).await? {
         return Ok(response);
@@ -87,8 +156,18 @@ async fn handle_auth_request(
     request: &Request<'_>,
 ) -> Result<Option<Response>> {
     match request.request_type() {
-        RequestType::SaslStart => Ok(Some(handle_sasl_start(ctx, request).await?)),
-        RequestType::SaslContinue => Ok(Some(handle_sasl_continue(ctx, request).await?)),
+        RequestType::SaslStart | RequestType::SaslContinue | RequestType::Authenticate => {
+            check_ip_throttle(ctx).await?;
+            let response = match request.request_type() {
+                RequestType::SaslStart => handle_sasl_start(ctx, request).await,
+                RequestType::SaslContinue => handle_sasl_continue(ctx, request).await,
+                _ => handle_authenticate(ctx, request).await,
+            };
+            if response.is_ok() {
+                record_success(ctx).await;
+            }
+            Ok(Some(response?))
+        }
         RequestType::Logout => {
             ctx.auth_state = AuthState::new();
             Ok(Some(Response::Raw(RawResponse(rawdoc! {
@@ -100,17 +179,60 @@ async fn handle_auth_request(
 }
 
 async fn handle_sasl_start(ctx: &mut ConnectionContext, request: &Request<'_>) -> Result<Response> {
+    let mut response = sasl_start(ctx, request).await?;
+    response.append("ok", OK_SUCCEEDED);
+    Ok(Response::Raw(RawResponse(response)))
+}
+
+/// Runs the saslStart step of a speculativeAuthenticate document sent with hello.
+/// A failure is not an error for hello, the client falls back to a full conversation when the reply is missing.
+pub async fn speculative_authenticate(
+    ctx: &mut ConnectionContext,
+    document: &RawDocument,
+) -> Option<RawDocumentBuf> {
+    if !matches!(document.get("saslStart"), Ok(Some(_))) {
+        return None;
+    }
+
+    let request = Request::Raw(RequestType::SaslStart, document, None);
+    let response = match check_ip_throttle(ctx).await {
+        Ok(()) => sasl_start(ctx, &request).await,
+        Err(e) => Err(e),
+    };
+    if response.is_ok() {
+        record_success(ctx).await;
+    }
+    match response {
+        Ok(response) => Some(response),
+        Err(e) => {
+            log::debug!("Speculative authentication failed: {}", e);
+            ctx.auth_state = AuthState::new();
+            None
+        }
+    }
+}
+
+async fn sasl_start(ctx: &mut ConnectionContext, request: &Request<'_>) -> Result<RawDocumentBuf> {
     let mechanism = request
         .document()
         .get_str("mechanism")
         .map_err(DocumentDBError::parse_failure())?;
 
-    if mechanism != "SCRAM-SHA-256" {
-        return Err(DocumentDBError::unauthorized(
-            "Only SCRAM-SHA-256 is supported".to_string(),
-        ));
+    // A new conversation abandons any unfinished one on this connection
+    ctx.auth_state.first_state = None;
+    ctx.auth_state.oidc_started = false;
+
+    if mechanism == PLAIN_MECHANISM {
+        return sasl_start_plain(ctx, request).await;
+    }
+    if mechanism == OIDC_MECHANISM {
+        return sasl_start_oidc(ctx, request).await;
     }
 
+    let mechanism = ScramMechanism::from_name(mechanism).ok_or(DocumentDBError::unauthorized(
+        "Only SCRAM-SHA-1, SCRAM-SHA-256, PLAIN and MONGODB-OIDC are supported".to_string(),
+    ))?;
+
     let payload = parse_sasl_payload(request, true)?;
 
     let username = payload.username.ok_or(DocumentDBError::unauthorized(
@@ -121,6 +243,8 @@ async fn handle_sasl_start(ctx: &mut ConnectionContext, request: &Request<'_>) -
         "Nonce missing from SaslStart.".to_string(),
     ))?;
 
+    begin_user_attempt(ctx, username).await?;
+
     let mut nonce = String::with_capacity(client_nonce.len() + NONCE_LENGTH);
     nonce.push_str(client_nonce);
     nonce.extend(
@@ -130,34 +254,191 @@ async fn handle_sasl_start(ctx: &mut ConnectionContext, request: &Request<'_>) -
             .take(NONCE_LENGTH),
     );
 
-    let (salt, iterations) = get_salt_and_iteration(ctx, username).await?;
+    let keys = cached_scram_keys(ctx, mechanism, username).await;
+    let (salt, iterations) = match keys.as_ref() {
+        Some(keys) => (keys.salt.clone(), keys.iterations),
+        None => get_salt_and_iteration(ctx, mechanism, username).await?,
+    };
     let response = format!("r={},s={},i={}", nonce, salt, iterations);
 
     ctx.auth_state.first_state = Some(ScramFirstState {
+        mechanism,
+        keys,
         nonce,
         first_message_bare: format!("n={},r={}", username, client_nonce),
         first_message: response.clone(),
     });
 
-    ctx.auth_state.username = Some(username.to_string());
-
     let binary_response = bson::Binary {
         subtype: BinarySubtype::Generic,
         bytes: response.as_bytes().to_vec(),
     };
 
-    Ok(Response::Raw(RawResponse(rawdoc! {
+    Ok(rawdoc! {
         "payload": binary_response,
-        "ok": OK_SUCCEEDED,
         "conversationId": 1,
         "done": false
-    })))
+    })
+}
+
+/// PLAIN sends the password itself, so it is only accepted over TLS.
+/// The conversation completes in one step, and the password is kept to open the user's data pool.
+async fn sasl_start_plain(
+    ctx: &mut ConnectionContext,
+    request: &Request<'_>,
+) -> Result<RawDocumentBuf> {
+    if !ctx.tls {
+        return Err(DocumentDBError::unauthorized(
+            "PLAIN authentication is only allowed over TLS".to_string(),
+        ));
+    }
+
+    let payload = request
+        .document()
+        .get_binary("payload")
+        .map_err(DocumentDBError::parse_failure())?;
+    let payload = from_utf8(payload.bytes).map_err(|_| DocumentDBError::sasl_payload_invalid())?;
+
+    // [authzid] NUL authcid NUL passwd
+    let mut parts = payload.split('\0');
+    let (Some(authorization_id), Some(username), Some(password), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(DocumentDBError::sasl_payload_invalid());
+    };
+    if username.is_empty() || (!authorization_id.is_empty() && authorization_id != username) {
+        return Err(DocumentDBError::sasl_payload_invalid());
+    }
+
+    begin_user_attempt(ctx, username).await?;
+    check_blocked_role_prefixes(ctx, username)?;
+
+    let authenticated: bool = ctx
+        .service_context
+        .authentication_connection()
+        .await?
+        .query(
+            ctx.service_context.query_catalog().authenticate_with_pwd(),
+            &[Type::TEXT, Type::TEXT],
+            &[&username, &password],
+            None,
+            &mut RequestInfo::new(),
+        )
+        .await?
+        .first()
+        .ok_or(DocumentDBError::pg_response_empty())?
+        .try_get(0)?;
+    if !authenticated {
+        record_credentials_failure(ctx).await;
+        return Err(DocumentDBError::documentdb_error(
+            ErrorCode::AuthenticationFailed,
+            "Authentication failed.".to_string(),
+        ));
+    }
+
+    ctx.auth_state = AuthState::new();
+    ctx.auth_state.set_username(username);
+    ctx.auth_state.password = Some(password.to_string());
+    ctx.auth_state.authorized = true;
+
+    Ok(rawdoc! {
+        "payload": bson::Binary {
+            subtype: BinarySubtype::Generic,
+            bytes: Vec::new(),
+        },
+        "conversationId": 1,
+        "done": true
+    })
+}
+
+/// MONGODB-OIDC takes the token in saslStart, or in saslContinue after the client has asked for the identity provider.
+async fn sasl_start_oidc(
+    ctx: &mut ConnectionContext,
+    request: &Request<'_>,
+) -> Result<RawDocumentBuf> {
+    let options = oidc_options(ctx)?;
+
+    let payload = oidc_payload(request)?;
+    if let Ok(token) = payload.get_str("jwt") {
+        return authenticate_with_token(ctx, token).await;
+    }
+
+    ctx.auth_state = AuthState::new();
+    ctx.auth_state.oidc_started = true;
+
+    let mut provider = rawdoc! { "issuer": options.issuer };
+    if let Some(client_id) = options.client_id {
+        provider.append("clientId", client_id);
+    }
+    Ok(rawdoc! {
+        "payload": bson::Binary {
+            subtype: BinarySubtype::Generic,
+            bytes: provider.into_bytes(),
+        },
+        "conversationId": 1,
+        "done": false
+    })
+}
+
+fn oidc_options(ctx: &ConnectionContext) -> Result<OidcOptions> {
+    ctx.service_context
+        .setup_configuration()
+        .oidc_options()
+        .ok_or(DocumentDBError::documentdb_error(
+            ErrorCode::MechanismUnavailable,
+            "MONGODB-OIDC authentication is not enabled".to_string(),
+        ))
+}
+
+fn oidc_payload<'a>(request: &'a Request<'_>) -> Result<&'a RawDocument> {
+    let payload = request
+        .document()
+        .get_binary("payload")
+        .map_err(DocumentDBError::parse_failure())?;
+    RawDocument::from_bytes(payload.bytes).map_err(|_| DocumentDBError::sasl_payload_invalid())
+}
+
+async fn authenticate_with_token(
+    ctx: &mut ConnectionContext,
+    token: &str,
+) -> Result<RawDocumentBuf> {
+    let options = oidc_options(ctx)?;
+    let key_set = ctx.service_context.jwks_cache().get(&options).await?;
+    let identity = oidc::validate_token(&options, &key_set, token)?;
+
+    begin_user_attempt(ctx, &identity.username).await?;
+    check_blocked_role_prefixes(ctx, &identity.username)?;
+    ensure_user_exists(ctx, &identity.username).await?;
+
+    ctx.auth_state = AuthState::new();
+    ctx.auth_state.set_username(&identity.username);
+    ctx.auth_state.password = Some("".to_string());
+    ctx.auth_state.expires_at = Some(identity.expires_at);
+    ctx.auth_state.authorized = true;
+
+    Ok(rawdoc! {
+        "payload": bson::Binary {
+            subtype: BinarySubtype::Generic,
+            bytes: Vec::new(),
+        },
+        "conversationId": 1,
+        "done": true
+    })
 }
 
 async fn handle_sasl_continue(
     ctx: &mut ConnectionContext,
     request: &Request<'_>,
 ) -> Result<Response> {
+    if ctx.auth_state.oidc_started {
+        let token = oidc_payload(request)?
+            .get_str("jwt")
+            .map_err(|_| DocumentDBError::sasl_payload_invalid())?;
+        let mut response = authenticate_with_token(ctx, token).await?;
+        response.append("ok", OK_SUCCEEDED);
+        return Ok(Response::Raw(RawResponse(response)));
+    }
+
     let payload = parse_sasl_payload(request, false)?;
 
     if let Some(first_state) = ctx.auth_state.first_state.as_ref() {
@@ -195,39 +476,17 @@ async fn handle_sasl_continue(
             client_nonce
         );
 
-        let scram_sha256_row = ctx
-            .service_context
-            .authentication_connection()
-            .await?
-            .query(
-                ctx.service_context
-                    .query_catalog()
-                    .authenticate_with_scram_sha256(),
-                &[Type::TEXT, Type::TEXT, Type::TEXT],
-                &[&username, &auth_message, &proof],
-                None,
-                &mut RequestInfo::new(),
-            )
-            .await?;
-
-        let scram_sha256_doc: PgDocument = scram_sha256_row
-            .first()
-            .ok_or(DocumentDBError::pg_response_empty())?
-            .try_get(0)?;
-
-        if scram_sha256_doc
-            .0
-            .get_i32("ok")
-            .map_err(DocumentDBError::pg_response_invalid)?
-            != 1
-        {
+        let server_signature = match first_state.keys.as_ref() {
+            Some(keys) => keys.verify_proof(&auth_message, proof)?,
+            None => {
+                verify_proof_in_backend(ctx, first_state.mechanism, username, &auth_message, proof)
+                    .await?
+            }
+        };
+        let Some(server_signature) = server_signature else {
+            record_credentials_failure(ctx).await;
             return Err(DocumentDBError::unauthorized("Invalid key".to_string()));
-        }
-
-        let server_signature = scram_sha256_doc
-            .0
-            .get_str("ServerSignature")
-            .map_err(DocumentDBError::pg_response_invalid)?;
+        };
 
         let payload = bson::Binary {
             subtype: BinarySubtype::Generic,
@@ -250,6 +509,232 @@ async fn handle_sasl_continue(
     }
 }
 
+async fn verify_proof_in_backend(
+    ctx: &ConnectionContext,
+    mechanism: ScramMechanism,
+    username: &str,
+    auth_message: &str,
+    proof: &str,
+) -> Result<Option<String>> {
+    let scram_row = ctx
+        .service_context
+        .authentication_connection()
+        .await?
+        .query(
+            mechanism.authenticate_query(ctx.service_context.query_catalog()),
+            &[Type::TEXT, Type::TEXT, Type::TEXT],
+            &[&username, &auth_message, &proof],
+            None,
+            &mut RequestInfo::new(),
+        )
+        .await?;
+
+    let scram_doc: PgDocument = scram_row
+        .first()
+        .ok_or(DocumentDBError::pg_response_empty())?
+        .try_get(0)?;
+
+    if scram_doc
+        .0
+        .get_i32("ok")
+        .map_err(DocumentDBError::pg_response_invalid)?
+        != 1
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        scram_doc
+            .0
+            .get_str("ServerSignature")
+            .map_err(DocumentDBError::pg_response_invalid)?
+            .to_string(),
+    ))
+}
+
+/// Handles the authenticate command, which only supports MONGODB-X509.
+/// The client certificate was verified against the CA during the TLS handshake, the user is identified by its subject.
+async fn handle_authenticate(
+    ctx: &mut ConnectionContext,
+    request: &Request<'_>,
+) -> Result<Response> {
+    let mechanism = request
+        .document()
+        .get_str("mechanism")
+        .map_err(DocumentDBError::parse_failure())?;
+    if mechanism != "MONGODB-X509" {
+        return Err(DocumentDBError::documentdb_error(
+            ErrorCode::MechanismUnavailable,
+            format!("Unsupported authentication mechanism: {}", mechanism),
+        ));
+    }
+
+    if request.db()? != EXTERNAL_DATABASE {
+        return Err(DocumentDBError::bad_value(
+            "X.509 authentication must be done on the $external database".to_string(),
+        ));
+    }
+
+    let certificate = ctx
+        .client_certificate
+        .as_ref()
+        .ok_or(DocumentDBError::documentdb_error(
+            ErrorCode::AuthenticationFailed,
+            "No verified client certificate was presented during the TLS handshake".to_string(),
+        ))?;
+    let subject = certificate_subject(certificate.subject_name())?;
+
+    // A user given by the client must name the certificate's subject
+    if let Ok(user) = request.document().get_str("user") {
+        if user != subject {
+            return Err(DocumentDBError::documentdb_error(
+                ErrorCode::AuthenticationFailed,
+                "Username does not match the client certificate subject".to_string(),
+            ));
+        }
+    }
+
+    begin_user_attempt(ctx, &subject).await?;
+    check_blocked_role_prefixes(ctx, &subject)?;
+    ensure_user_exists(ctx, &subject).await?;
+
+    ctx.auth_state = AuthState::new();
+    ctx.auth_state.set_username(&subject);
+    ctx.auth_state.password = Some("".to_string());
+    ctx.auth_state.authorized = true;
+
+    Ok(Response::Raw(RawResponse(rawdoc! {
+        "dbname": EXTERNAL_DATABASE,
+        "user": subject,
+        "ok": OK_SUCCEEDED,
+    })))
+}
+
+fn client_throttle_key(ctx: &ConnectionContext) -> AuthThrottleKey {
+    match ctx.unix_peer_uid {
+        Some(uid) => AuthThrottleKey::UnixPeer(uid),
+        None => AuthThrottleKey::Ip(ctx.ip.ip()),
+    }
+}
+
+async fn check_ip_throttle(ctx: &ConnectionContext) -> Result<()> {
+    ctx.service_context
+        .auth_throttle()
+        .check(&client_throttle_key(ctx))
+        .await
+}
+
+// Records who is authenticating, so that a failure is also counted against the username.
+async fn begin_user_attempt(ctx: &mut ConnectionContext, username: &str) -> Result<()> {
+    ctx.auth_state.set_username(username);
+    ctx.service_context
+        .auth_throttle()
+        .check(&AuthThrottleKey::User(username.to_string()))
+        .await
+}
+
+fn user_throttle_key(ctx: &ConnectionContext) -> Option<AuthThrottleKey> {
+    ctx.auth_state
+        .username
+        .as_ref()
+        .map(|username| AuthThrottleKey::User(username.clone()))
+}
+
+async fn record_success(ctx: &ConnectionContext) {
+    if !ctx.auth_state.authorized {
+        return;
+    }
+    if let Some(user_key) = user_throttle_key(ctx) {
+        ctx.service_context
+            .auth_throttle()
+            .record_success(&user_key)
+            .await;
+    }
+}
+
+// Only a wrong proof or password counts against the client and the user, not a malformed or unsupported request.
+async fn record_credentials_failure(ctx: &ConnectionContext) {
+    for key in [Some(client_throttle_key(ctx)), user_throttle_key(ctx)]
+        .into_iter()
+        .flatten()
+    {
+        record_failure(ctx, key).await;
+    }
+}
+
+async fn record_failure(ctx: &ConnectionContext, key: AuthThrottleKey) {
+    let throttle = ctx.service_context.auth_throttle();
+    if let Some(lockout) = throttle.record_failure(key.clone()).await {
+        log::warn!(
+            "Locked out {} for {} seconds after repeated authentication failures",
+            key,
+            lockout.as_secs()
+        );
+        if let Some(telemetry) = ctx.telemetry_provider.as_ref() {
+            telemetry.emit_auth_lockout_event(ctx, &key, lockout).await;
+        }
+    }
+}
+
+async fn ensure_user_exists(ctx: &ConnectionContext, username: &str) -> Result<()> {
+    let exists: bool = ctx
+        .service_context
+        .system_requests_connection()
+        .await?
+        .query(
+            ctx.service_context.query_catalog().user_exists(),
+            &[Type::TEXT],
+            &[&username],
+            None,
+            &mut RequestInfo::new(),
+        )
+        .await?
+        .first()
+        .ok_or(DocumentDBError::pg_response_empty())?
+        .try_get(0)?;
+    if !exists {
+        return Err(DocumentDBError::documentdb_error(
+            ErrorCode::AuthenticationFailed,
+            "Invalid account: User details not found in the database".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the RFC 2253 form of a certificate subject, which names the user.
+/// The whole subject is used so that certificates which only share a common name map to different users.
+pub fn certificate_subject(name: &X509NameRef) -> Result<String> {
+    let mut parts = Vec::new();
+    for entry in name.entries() {
+        let value = entry.data().as_utf8()?.to_string();
+        let key = entry.object().nid().short_name()?;
+
+        let mut escaped = String::with_capacity(value.len());
+        let last = value.chars().count().saturating_sub(1);
+        for (i, c) in value.chars().enumerate() {
+            if matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';')
+                || (i == 0 && matches!(c, ' ' | '#'))
+                || (i == last && c == ' ')
+            {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        parts.push(format!("{}={}", key, escaped));
+    }
+
+    if parts.is_empty() {
+        return Err(DocumentDBError::documentdb_error(
+            ErrorCode::AuthenticationFailed,
+            "Client certificate has an empty subject".to_string(),
+        ));
+    }
+
+    // RFC 2253 lists the most specific attribute first
+    parts.reverse();
+    Ok(parts.join(","))
+}
+
 struct ScramPayload<'a> {
     username: Option<&'a str>,
     nonce: Option<&'a str>,
@@ -318,7 +803,7 @@ fn parse_sasl_payload<'a, 'b: 'a>(
     })
 }
 
-async fn get_salt_and_iteration(ctx: &ConnectionContext, username: &str) -> Result<(String, i32)> {
+fn check_blocked_role_prefixes(ctx: &ConnectionContext, username: &str) -> Result<()> {
     for blocked_prefix in ctx
         .service_context
         .setup_configuration()
@@ -333,13 +818,112 @@ async fn get_salt_and_iteration(ctx: &ConnectionContext, username: &str) -> Resu
             ));
         }
     }
+    Ok(())
+}
+
+async fn get_salt_and_iteration(
+    ctx: &ConnectionContext,
+    mechanism: ScramMechanism,
+    username: &str,
+) -> Result<(String, i32)> {
+    check_blocked_role_prefixes(ctx, username)?;
+
+    lookup_salt_and_iterations(ctx, mechanism, username)
+        .await?
+        .ok_or(DocumentDBError::documentdb_error(
+            ErrorCode::AuthenticationFailed,
+            "Invalid account: User details not found in the database".to_string(),
+        ))
+}
+
+/// Returns the user's SCRAM-SHA-256 keys when the cache is enabled, loading them on a miss.
+/// Without keys the proof is verified by the backend, as it is when the secret cannot be read.
+async fn cached_scram_keys(
+    ctx: &ConnectionContext,
+    mechanism: ScramMechanism,
+    username: &str,
+) -> Option<Arc<ScramKeys>> {
+    let cache = ctx.service_context.scram_cache();
+    if mechanism != ScramMechanism::Sha256
+        || !cache.enabled()
+        || check_blocked_role_prefixes(ctx, username).is_err()
+    {
+        return None;
+    }
+
+    let generation = ctx.service_context.dynamic_configuration().generation();
+    if let Some(keys) = cache.get(username, generation).await {
+        return Some(keys);
+    }
+
+    match load_scram_keys(ctx, username).await {
+        Ok(Some(keys)) => {
+            let keys = Arc::new(keys);
+            cache.insert(username, keys.clone(), generation).await;
+            Some(keys)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            log::warn!("Failed to load SCRAM keys of {}: {}", username, e);
+            None
+        }
+    }
+}
+
+async fn load_scram_keys(ctx: &ConnectionContext, username: &str) -> Result<Option<ScramKeys>> {
+    let results = ctx
+        .service_context
+        .authentication_connection()
+        .await?
+        .query(
+            ctx.service_context.query_catalog().scram_sha256_secret(),
+            &[Type::TEXT],
+            &[&username],
+            None,
+            &mut RequestInfo::new(),
+        )
+        .await?;
+
+    let secret: Option<String> = match results.first() {
+        Some(row) => row.try_get(0)?,
+        None => None,
+    };
+    Ok(secret.as_deref().and_then(ScramKeys::parse))
+}
+
+/// Returns whether the user has credentials for the given SCRAM mechanism.
+/// The answer reveals whether the user exists, so nothing is looked up while the client address is throttled,
+/// and a lookup that finds no SCRAM-SHA-256 credentials, which every user has, counts as a failed attempt.
+pub async fn has_scram_credentials(
+    ctx: &ConnectionContext,
+    mechanism: ScramMechanism,
+    username: &str,
+) -> Result<bool> {
+    if check_ip_throttle(ctx).await.is_err() || check_blocked_role_prefixes(ctx, username).is_err()
+    {
+        return Ok(false);
+    }
 
+    let found = lookup_salt_and_iterations(ctx, mechanism, username)
+        .await?
+        .is_some();
+    if !found && mechanism == ScramMechanism::Sha256 {
+        record_failure(ctx, client_throttle_key(ctx)).await;
+    }
+    Ok(found)
+}
+
+async fn lookup_salt_and_iterations(
+    ctx: &ConnectionContext,
+    mechanism: ScramMechanism,
+    username: &str,
+) -> Result<Option<(String, i32)>> {
     let results = ctx
         .service_context
         .authentication_connection()
         .await?
         .query(
-            ctx.service_context.query_catalog().salt_and_iterations(),
+            mechanism.salt_and_iterations_query(ctx.service_context.query_catalog()),
             &[Type::TEXT],
             &[&username],
             None,
@@ -357,10 +941,7 @@ async fn get_salt_and_iteration(ctx: &ConnectionContext, username: &str) -> Resu
         .map_err(|e| DocumentDBError::internal_error(e.to_string()))?
         != 1
     {
-        return Err(DocumentDBError::documentdb_error(
-            ErrorCode::AuthenticationFailed,
-            "Invalid account: User details not found in the database".to_string(),
-        ));
+        return Ok(None);
     }
 
     let iterations = doc
@@ -372,5 +953,5 @@ async fn get_salt_and_iteration(ctx: &ConnectionContext, username: &str) -> Resu
         .get_str("salt")
         .map_err(DocumentDBError::pg_response_invalid)?;
 
-    Ok((salt.to_string(), iterations))
+    Ok(Some((salt.to_string(), iterations)))
 }
@@ -10,17 +10,51 @@ use std::io::Cursor;
 
 use bson::{spec::ElementType, RawBsonRef, RawDocument};
 
-use crate::{error::DocumentDBError, protocol::util::SyncLittleEndianRead};
+use crate::{
+    error::{DocumentDBError, ErrorCode},
+    protocol::{util::SyncLittleEndianRead, MAX_BSON_INTERNAL_SIZE, MIN_BSON_OBJECT_SIZE},
+};
 
 /// Read a document's raw BSON bytes from the provided reader.
+/// The length prefix is validated against the remaining bytes before slicing.
 pub fn read_document_bytes<'a>(
     cursor: &mut Cursor<&'a [u8]>,
 ) -> Result<(&'a RawDocument, usize), DocumentDBError> {
-    let data = &cursor.clone().into_inner()[cursor.position() as usize..];
+    let data = cursor
+        .get_ref()
+        .get(cursor.position() as usize..)
+        .unwrap_or_default();
+    if data.len() < std::mem::size_of::<i32>() {
+        return Err(DocumentDBError::protocol_error(format!(
+            "Document length is truncated with {} bytes remaining",
+            data.len()
+        )));
+    }
     let length = cursor.read_i32_sync()?;
-    let doc = RawDocument::from_bytes(&data[0..length as usize])?;
+    let length = validate_document_length(length, data.len())?;
+    let doc = RawDocument::from_bytes(&data[0..length])?;
     cursor.set_position(cursor.position() + length as u64 - 4);
-    Ok((doc, length as usize))
+    Ok((doc, length))
+}
+
+/// Validates a BSON length prefix against the size limits and the number of bytes available.
+fn validate_document_length(length: i32, available: usize) -> Result<usize, DocumentDBError> {
+    if length > MAX_BSON_INTERNAL_SIZE {
+        return Err(DocumentDBError::documentdb_error(
+            ErrorCode::BSONObjectTooLarge,
+            format!(
+                "Document of {} bytes exceeds the maximum of {} bytes",
+                length, MAX_BSON_INTERNAL_SIZE
+            ),
+        ));
+    }
+    if length < MIN_BSON_OBJECT_SIZE || length as usize > available {
+        return Err(DocumentDBError::protocol_error(format!(
+            "Document length {} is invalid with {} bytes remaining",
+            length, available
+        )));
+    }
+    Ok(length as usize)
 }
 
 pub fn convert_to_f64(bson: RawBsonRef) -> Option<f64> {
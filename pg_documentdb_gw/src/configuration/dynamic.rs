@@ -29,6 +29,11 @@ pub trait DynamicConfiguration: Send + Sync + Debug {
     // Needed to downcast to concrete type
     fn as_any(&self) -> &dyn std::any::Any;
 
+    /// Changes whenever the configuration values change, so that state derived from them can be dropped.
+    fn generation(&self) -> u64 {
+        0
+    }
+
     async fn enable_change_streams(&self) -> bool {
         self.get_bool("enableChangeStreams", false).await
     }
@@ -13,12 +13,18 @@ mod version;
 
 pub use dynamic::DynamicConfiguration;
 pub use pg_configuration::PgConfiguration;
-pub use setup::{CertificateOptions, DocumentDBSetupConfiguration};
+pub use setup::{
+    AuthThrottleOptions, CertificateOptions, ClientCertificateMode, DocumentDBSetupConfiguration,
+    OidcOptions,
+};
 pub use version::Version;
 
 use dyn_clone::{clone_trait_object, DynClone};
+use ipnet::IpNet;
 use std::fmt::Debug;
 
+use crate::protocol::compression::Compressor;
+
 // These are the required configuration fields.
 /// A trait that defines the configuration setup for the application.
 /// Implementors of this trait are expected to provide various configuration
@@ -55,9 +61,26 @@ pub trait SetupConfiguration: DynClone + Send + Sync + Debug {
     /// Returns the port number on which the gateway listens.
     fn gateway_listen_port(&self) -> u16;
 
+    /// Returns the path of the Unix domain socket to serve on alongside TCP, if any.
+    /// Connections over the socket do not use TLS.
+    fn unix_socket_path(&self) -> Option<String>;
+
+    /// Returns the file mode applied to the Unix domain socket.
+    fn unix_socket_file_permissions(&self) -> u32;
+
+    /// Indicates whether connections from trusted proxies start with a PROXY protocol v1 or v2 header.
+    fn enable_proxy_protocol(&self) -> bool;
+
+    /// Returns the networks of the proxies which are trusted to send a PROXY protocol header.
+    fn trusted_proxy_cidrs(&self) -> Vec<IpNet>;
+
     /// Indicates whether SSL/TCP enforcement is enabled.
     fn enforce_ssl_tcp(&self) -> bool;
 
+    /// Indicates whether TLS and plaintext connections are both accepted on the TCP port,
+    /// chosen per connection from its first bytes. Takes precedence over `enforce_ssl_tcp`.
+    fn detect_ssl_tcp(&self) -> bool;
+
     /// Returns a list of role prefixes that are blocked.
     fn blocked_role_prefixes(&self) -> &[String];
 
@@ -70,9 +93,30 @@ pub trait SetupConfiguration: DynClone + Send + Sync + Debug {
     /// Returns the certificate options for SSL/TLS connections.
     fn certificate_options(&self) -> Option<CertificateOptions>;
 
+    /// Returns the interval (in seconds) at which the certificate files are checked for changes.
+    fn certificate_refresh_interval_secs(&self) -> u32;
+
+    /// Returns the options for MONGODB-OIDC authentication, which is disabled when absent.
+    fn oidc_options(&self) -> Option<OidcOptions>;
+
+    /// Returns the limits on failed authentication attempts.
+    fn auth_throttle_options(&self) -> AuthThrottleOptions;
+
+    /// Returns how long (in seconds) SCRAM-SHA-256 keys are cached to verify proofs in the gateway, 0 disables the cache.
+    fn scram_cache_ttl_secs(&self) -> u64;
+
     /// Returns the name of the Gateway application.
     fn application_name(&self) -> &str;
 
+    /// Returns the wire protocol compressors which may be negotiated with clients, in order of preference.
+    fn compressors(&self) -> Vec<Compressor>;
+
+    /// Indicates whether OP_MSG responses should carry a CRC-32C checksum.
+    fn enable_response_checksums(&self) -> bool;
+
+    /// Indicates whether cursor batches in OP_MSG responses should be sent as a document sequence section.
+    fn enable_response_document_sequences(&self) -> bool;
+
     /// Provides a way to downcast the trait object to a concrete type.
     fn as_any(&self) -> &dyn std::any::Any;
 }
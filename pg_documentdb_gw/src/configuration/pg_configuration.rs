@@ -7,6 +7,7 @@
  */
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -34,6 +35,7 @@ pub struct HostConfig {
 #[derive(Debug)]
 pub struct PgConfiguration {
     values: RwLock<HashMap<String, String>>,
+    generation: AtomicU64,
 }
 
 impl PgConfiguration {
@@ -67,7 +69,10 @@ impl PgConfiguration {
                         {
                             Ok(new_config) => {
                                 let mut config_self_writable = configuration.values.write().await;
-                                *config_self_writable = new_config;
+                                if *config_self_writable != new_config {
+                                    *config_self_writable = new_config;
+                                    configuration.generation.fetch_add(1, Ordering::Relaxed);
+                                }
                             }
                             Err(e) => log::error!("Failed to refresh configuration: {}", e),
                         }
@@ -101,7 +106,10 @@ impl PgConfiguration {
             .await?,
         );
 
-        let configuration = Arc::new(PgConfiguration { values });
+        let configuration = Arc::new(PgConfiguration {
+            values,
+            generation: AtomicU64::new(0),
+        });
 
         let refresh_interval = setup_configuration.dynamic_configuration_refresh_interval_secs();
         Self::start_dynamic_configuration_refresh_thread(
@@ -242,4 +250,8 @@ impl DynamicConfiguration for PgConfiguration {
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
+
+    fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
 }
@@ -6,13 +6,15 @@
  *-------------------------------------------------------------------------
  */
 
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
+use ipnet::IpNet;
 use serde::Deserialize;
 use tokio::fs::File;
 
 use super::SetupConfiguration;
 use crate::error::{DocumentDBError, Result};
+use crate::protocol::compression::Compressor;
 
 // Configurations which are populated statically on process start
 #[derive(Debug, Deserialize, Default, Clone)]
@@ -25,6 +27,12 @@ pub struct DocumentDBSetupConfiguration {
     // Gateway listener configuration
     pub use_local_host: Option<bool>,
     pub gateway_listen_port: Option<u16>,
+    pub unix_socket_path: Option<String>,
+    // Octal file mode of the Unix socket, such as "0660"
+    pub unix_socket_file_permissions: Option<String>,
+    pub enable_proxy_protocol: Option<bool>,
+    // Peers allowed to send a PROXY header, in CIDR notation
+    pub trusted_proxy_cidrs: Option<Vec<String>>,
 
     // Postgres configuration
     pub postgres_system_user: Option<String>,
@@ -37,21 +45,120 @@ pub struct DocumentDBSetupConfiguration {
     pub transaction_timeout_secs: Option<u64>,
     pub cursor_timeout_secs: Option<u64>,
     pub enforce_ssl_tcp: Option<bool>,
+    pub detect_ssl_tcp: Option<bool>,
     pub certificate_options: Option<CertificateOptions>,
+    pub certificate_refresh_interval_secs: Option<u32>,
+    pub oidc_options: Option<OidcOptions>,
+    pub auth_throttle_options: Option<AuthThrottleOptions>,
+    // How long SCRAM-SHA-256 keys are cached to verify proofs in the gateway, disabled when absent
+    pub scram_cache_ttl_secs: Option<u64>,
 
     #[serde(default)]
     pub dynamic_configuration_file: String,
     pub dynamic_configuration_refresh_interval_secs: Option<u32>,
     pub postgres_command_timeout_secs: Option<u64>,
+
+    // Wire protocol compressors offered to clients, by name
+    pub compressors: Option<Vec<String>>,
+    pub enable_response_checksums: Option<bool>,
+    pub enable_response_document_sequences: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Default, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct CertificateOptions {
+    // "PemFile" for a PEM certificate chain and key file, or "Pkcs12" for a single PKCS#12 file
     pub cert_type: String,
     pub file_path: String,
+    #[serde(default)]
     pub key_file_path: String,
     pub ca_path: Option<String>,
+
+    // Password of a PKCS#12 file
+    pub password: Option<String>,
+
+    // Bounds of the negotiated protocol version, such as "TLS1.2"
+    pub min_tls_version: Option<String>,
+    pub max_tls_version: Option<String>,
+
+    // OpenSSL cipher list used up to TLS 1.2, and ciphersuites used by TLS 1.3
+    pub cipher_list: Option<String>,
+    pub cipher_suites: Option<String>,
+
+    #[serde(default)]
+    pub client_certificate_mode: ClientCertificateMode,
+}
+
+/// Whether clients are asked for a certificate, which is verified against the CA file.
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ClientCertificateMode {
+    #[default]
+    None,
+    Optional,
+    Require,
+}
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct OidcOptions {
+    // JSON Web Key Set holding the identity provider's public signing keys
+    pub jwks_file_path: String,
+    pub issuer: String,
+    pub audience: String,
+
+    // Claim naming the Postgres role of the user, "sub" by default
+    pub principal_claim: Option<String>,
+
+    // Client ID returned to drivers which ask for the identity provider
+    pub client_id: Option<String>,
+
+    // How long the keys read from the JWKS file are used before it is read again, 300 seconds by default
+    pub jwks_refresh_interval_secs: Option<u64>,
+}
+
+impl OidcOptions {
+    pub fn principal_claim(&self) -> &str {
+        self.principal_claim.as_deref().unwrap_or("sub")
+    }
+
+    pub fn jwks_refresh_interval(&self) -> Duration {
+        Duration::from_secs(self.jwks_refresh_interval_secs.unwrap_or(300))
+    }
+}
+
+// Limits on failed authentication attempts, counted per client address and per username
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct AuthThrottleOptions {
+    pub enabled: bool,
+    pub max_failures_per_ip: u32,
+    pub max_failures_per_user: u32,
+
+    // Failures older than this no longer count towards a lockout
+    pub failure_window_secs: u64,
+
+    // Failures within the window which are allowed before attempts are backed off
+    pub failures_before_backoff: u32,
+
+    // Attempts after further failures are rejected for a backoff which doubles with each failure
+    pub initial_backoff_millis: u64,
+    pub max_backoff_secs: u64,
+    pub lockout_secs: u64,
+}
+
+impl Default for AuthThrottleOptions {
+    fn default() -> Self {
+        AuthThrottleOptions {
+            enabled: false,
+            max_failures_per_ip: 20,
+            max_failures_per_user: 10,
+            failure_window_secs: 15 * 60,
+            failures_before_backoff: 3,
+            initial_backoff_millis: 250,
+            max_backoff_secs: 30,
+            lockout_secs: 5 * 60,
+        }
+    }
 }
 
 impl DocumentDBSetupConfiguration {
@@ -112,10 +219,56 @@ impl SetupConfiguration for DocumentDBSetupConfiguration {
         self.gateway_listen_port.unwrap_or(10260)
     }
 
+    fn unix_socket_path(&self) -> Option<String> {
+        self.unix_socket_path.clone()
+    }
+
+    fn unix_socket_file_permissions(&self) -> u32 {
+        const DEFAULT_UNIX_SOCKET_FILE_PERMISSIONS: u32 = 0o660;
+        match &self.unix_socket_file_permissions {
+            Some(mode) => u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+                .ok()
+                .filter(|mode| *mode <= 0o777)
+                .unwrap_or_else(|| {
+                    log::warn!(
+                        "Ignoring invalid Unix socket file permissions in configuration: {}",
+                        mode
+                    );
+                    DEFAULT_UNIX_SOCKET_FILE_PERMISSIONS
+                }),
+            None => DEFAULT_UNIX_SOCKET_FILE_PERMISSIONS,
+        }
+    }
+
+    fn enable_proxy_protocol(&self) -> bool {
+        self.enable_proxy_protocol.unwrap_or(false)
+    }
+
+    fn trusted_proxy_cidrs(&self) -> Vec<IpNet> {
+        self.trusted_proxy_cidrs
+            .iter()
+            .flatten()
+            .filter_map(|cidr| {
+                let net = cidr.parse::<IpNet>().ok();
+                if net.is_none() {
+                    log::warn!(
+                        "Ignoring invalid trusted proxy CIDR in configuration: {}",
+                        cidr
+                    );
+                }
+                net
+            })
+            .collect()
+    }
+
     fn enforce_ssl_tcp(&self) -> bool {
         self.enforce_ssl_tcp.unwrap_or(true)
     }
 
+    fn detect_ssl_tcp(&self) -> bool {
+        self.detect_ssl_tcp.unwrap_or(false)
+    }
+
     fn blocked_role_prefixes(&self) -> &[String] {
         &self.blocked_role_prefixes
     }
@@ -128,6 +281,28 @@ impl SetupConfiguration for DocumentDBSetupConfiguration {
         self.certificate_options.clone()
     }
 
+    fn certificate_refresh_interval_secs(&self) -> u32 {
+        match self.certificate_refresh_interval_secs {
+            Some(0) => {
+                log::warn!("Certificate refresh interval must be at least 1 second, using 1");
+                1
+            }
+            interval => interval.unwrap_or(60),
+        }
+    }
+
+    fn oidc_options(&self) -> Option<OidcOptions> {
+        self.oidc_options.clone()
+    }
+
+    fn auth_throttle_options(&self) -> AuthThrottleOptions {
+        self.auth_throttle_options.clone().unwrap_or_default()
+    }
+
+    fn scram_cache_ttl_secs(&self) -> u64 {
+        self.scram_cache_ttl_secs.unwrap_or(0)
+    }
+
     fn node_host_name(&self) -> &str {
         &self.node_host_name
     }
@@ -137,4 +312,28 @@ impl SetupConfiguration for DocumentDBSetupConfiguration {
             .as_deref()
             .unwrap_or("DocumentDBGateway")
     }
+
+    fn compressors(&self) -> Vec<Compressor> {
+        match &self.compressors {
+            Some(names) => names
+                .iter()
+                .filter_map(|name| {
+                    let compressor = Compressor::from_name(name);
+                    if compressor.is_none() {
+                        log::warn!("Ignoring unknown compressor in configuration: {}", name);
+                    }
+                    compressor
+                })
+                .collect(),
+            None => Compressor::SUPPORTED.to_vec(),
+        }
+    }
+
+    fn enable_response_checksums(&self) -> bool {
+        self.enable_response_checksums.unwrap_or(false)
+    }
+
+    fn enable_response_document_sequences(&self) -> bool {
+        self.enable_response_document_sequences.unwrap_or(false)
+    }
 }
@@ -0,0 +1,168 @@
+/*-------------------------------------------------------------------------
+ * Copyright (c) Microsoft Corporation.  All rights reserved.
+ *
+ * src/context/auth_throttle.rs
+ *
+ *-------------------------------------------------------------------------
+ */
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    net::IpAddr,
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::{sync::RwLock, task::JoinHandle, time::Instant};
+
+use crate::{
+    configuration::{AuthThrottleOptions, SetupConfiguration},
+    error::{DocumentDBError, ErrorCode, Result},
+};
+
+/// What failed authentication attempts are counted against.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AuthThrottleKey {
+    Ip(IpAddr),
+    // Unix socket clients have no address of their own, they are told apart by their user id
+    UnixPeer(u32),
+    User(String),
+}
+
+impl Display for AuthThrottleKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthThrottleKey::Ip(ip) => write!(f, "address {}", ip),
+            AuthThrottleKey::UnixPeer(uid) => write!(f, "unix socket peer with uid {}", uid),
+            AuthThrottleKey::User(user) => write!(f, "user {}", user),
+        }
+    }
+}
+
+struct FailureEntry {
+    failures: u32,
+    last_failure: Instant,
+    blocked_until: Instant,
+}
+
+// Counts failed authentication attempts per client address and per username.
+// Past the first few, each failure blocks further attempts for an exponentially growing backoff, and reaching
+// the failure limit locks the key out. Failures are forgotten after a quiet window.
+pub struct AuthThrottle {
+    options: AuthThrottleOptions,
+    entries: Arc<RwLock<HashMap<AuthThrottleKey, FailureEntry>>>,
+    _reaper: Option<JoinHandle<()>>,
+}
+
+impl AuthThrottle {
+    pub fn new(config: &dyn SetupConfiguration) -> Self {
+        let options = config.auth_throttle_options();
+        let entries: Arc<RwLock<HashMap<AuthThrottleKey, FailureEntry>>> =
+            Arc::new(RwLock::new(HashMap::new()));
+
+        let reaper = if options.enabled {
+            let entries_clone = entries.clone();
+            let failure_window = Duration::from_secs(options.failure_window_secs);
+            Some(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(60));
+                loop {
+                    interval.tick().await;
+                    let now = Instant::now();
+                    let mut entries = entries_clone.write().await;
+                    entries.retain(|_, v| {
+                        v.blocked_until > now || now - v.last_failure < failure_window
+                    })
+                }
+            }))
+        } else {
+            None
+        };
+
+        AuthThrottle {
+            options,
+            entries,
+            _reaper: reaper,
+        }
+    }
+
+    /// Fails while the key is backing off or locked out, before any backend query is made.
+    pub async fn check(&self, key: &AuthThrottleKey) -> Result<()> {
+        if !self.options.enabled {
+            return Ok(());
+        }
+
+        let entries = self.entries.read().await;
+        if let Some(entry) = entries.get(key) {
+            let now = Instant::now();
+            if entry.blocked_until > now {
+                return Err(DocumentDBError::documentdb_error(
+                    ErrorCode::AuthenticationFailed,
+                    format!(
+                        "Too many failed authentication attempts, retry in {} seconds",
+                        (entry.blocked_until - now).as_secs().max(1)
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts a failed attempt, returning the lockout duration when it reached the failure limit.
+    /// Attempts rejected while the key is blocked are not counted.
+    pub async fn record_failure(&self, key: AuthThrottleKey) -> Option<Duration> {
+        if !self.options.enabled {
+            return None;
+        }
+
+        let max_failures = match key {
+            AuthThrottleKey::Ip(_) | AuthThrottleKey::UnixPeer(_) => {
+                self.options.max_failures_per_ip
+            }
+            AuthThrottleKey::User(_) => self.options.max_failures_per_user,
+        };
+        let failure_window = Duration::from_secs(self.options.failure_window_secs);
+
+        let now = Instant::now();
+        let mut entries = self.entries.write().await;
+        let entry = entries.entry(key).or_insert(FailureEntry {
+            failures: 0,
+            last_failure: now,
+            blocked_until: now,
+        });
+        if entry.blocked_until > now {
+            return None;
+        }
+        if now - entry.last_failure >= failure_window {
+            entry.failures = 0;
+        }
+
+        entry.failures += 1;
+        entry.last_failure = now;
+
+        if entry.failures >= max_failures {
+            let lockout = Duration::from_secs(self.options.lockout_secs);
+            entry.failures = 0;
+            entry.blocked_until = now + lockout;
+            return Some(lockout);
+        }
+
+        if entry.failures < self.options.failures_before_backoff {
+            return None;
+        }
+
+        let backoff = Duration::from_millis(self.options.initial_backoff_millis)
+            .saturating_mul(
+                1 << (entry.failures - self.options.failures_before_backoff.max(1)).min(16),
+            )
+            .min(Duration::from_secs(self.options.max_backoff_secs));
+        entry.blocked_until = now + backoff;
+        None
+    }
+
+    pub async fn record_success(&self, key: &AuthThrottleKey) {
+        if self.options.enabled {
+            self.entries.write().await.remove(key);
+        }
+    }
+}
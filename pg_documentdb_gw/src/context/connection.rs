@@ -7,15 +7,17 @@
  */
 
 use std::{
+    collections::HashMap,
     net::SocketAddr,
     sync::{
         atomic::{AtomicI64, Ordering},
         Arc,
     },
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use bson::RawDocumentBuf;
+use openssl::x509::X509;
 
 use crate::telemetry::TelemetryProvider;
 use crate::{
@@ -23,6 +25,7 @@ use crate::{
     configuration::DynamicConfiguration,
     error::{DocumentDBError, Result},
     postgres::Connection,
+    protocol::compression::Compressor,
 };
 
 use super::{Cursor, CursorStoreEntry, ServiceContext};
@@ -33,12 +36,23 @@ pub struct ConnectionContext {
     pub service_context: Arc<ServiceContext>,
     pub auth_state: AuthState,
     pub requires_response: bool,
+    pub exhaust_allowed: bool,
+    pub legacy_cursor_reply: bool,
+    // OP_REPLY startingFrom of each open legacy cursor, with the time it was last used
+    pub legacy_cursor_positions: HashMap<i64, (i32, Instant)>,
     pub client_information: Option<RawDocumentBuf>,
     pub transaction: Option<(Vec<u8>, i64)>,
     pub telemetry_provider: Option<Box<dyn TelemetryProvider>>,
     pub ip: SocketAddr,
+    // The user id of the peer process for unix socket connections, which all share a placeholder address
+    pub unix_peer_uid: Option<u32>,
     pub cipher_type: i32,
     pub ssl_protocol: String,
+    // Whether the connection is served over TLS
+    pub tls: bool,
+    // The verified certificate presented by the client during the TLS handshake
+    pub client_certificate: Option<X509>,
+    pub compressors: Vec<Compressor>,
 }
 
 static CONNECTION_ID: AtomicI64 = AtomicI64::new(0);
@@ -48,7 +62,10 @@ impl ConnectionContext {
         if let Some((session_id, _)) = self.transaction.as_ref() {
             let transaction_store = self.service_context.transaction_store();
 
-            if let Some(conn) = transaction_store.get_connection(session_id).await {
+            if let Some(conn) = transaction_store
+                .get_connection(session_id, self.auth_state.username()?)
+                .await?
+            {
                 return Ok(conn);
             }
         }
@@ -57,6 +74,27 @@ impl ConnectionContext {
         ))
     }
 
+    /// Takes the position of a legacy cursor, which starts at 0 for cursors without a recorded position.
+    pub fn take_legacy_cursor_position(&mut self, cursor_id: i64) -> i32 {
+        self.legacy_cursor_positions
+            .remove(&cursor_id)
+            .map_or(0, |(position, _)| position)
+    }
+
+    /// Records the position of a legacy cursor. Positions of cursors which the cursor store
+    /// has reaped in the meantime are forgotten, so abandoned cursors do not accumulate.
+    pub fn set_legacy_cursor_position(&mut self, cursor_id: i64, position: i32) {
+        let cursor_timeout = Duration::from_secs(
+            self.service_context
+                .setup_configuration()
+                .cursor_timeout_secs(),
+        );
+        self.legacy_cursor_positions
+            .retain(|_, (_, last_used)| last_used.elapsed() < cursor_timeout);
+        self.legacy_cursor_positions
+            .insert(cursor_id, (position, Instant::now()));
+    }
+
     pub async fn get_cursor(&self, id: i64, user: &str) -> Option<CursorStoreEntry> {
         // If there is a transaction, get the cursor to its store
         if let Some((session_id, _)) = self.transaction.as_ref() {
@@ -136,12 +174,19 @@ impl ConnectionContext {
             service_context: Arc::new(sc),
             auth_state: AuthState::new(),
             requires_response: true,
+            exhaust_allowed: false,
+            legacy_cursor_reply: false,
+            legacy_cursor_positions: HashMap::new(),
             client_information: None,
             transaction: None,
             telemetry_provider,
             ip,
+            unix_peer_uid: None,
             cipher_type: 0,
             ssl_protocol,
+            tls: false,
+            client_certificate: None,
+            compressors: Vec::new(),
         }
     }
 
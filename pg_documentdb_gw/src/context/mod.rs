@@ -6,12 +6,22 @@
  *-------------------------------------------------------------------------
  */
 
+mod auth_throttle;
 mod connection;
 mod cursor;
+mod retryable_write;
+mod scram_cache;
 mod service;
+mod session;
 mod transaction;
 
+pub use auth_throttle::{AuthThrottle, AuthThrottleKey};
 pub use cursor::{Cursor, CursorStore, CursorStoreEntry};
+pub use retryable_write::{
+    RetryableWrite, RetryableWriteGuard, RetryableWriteKey, RetryableWriteStore,
+};
+pub use scram_cache::{ScramCache, ScramKeys};
+pub use session::{Session, SessionStore, LOGICAL_SESSION_TIMEOUT_MINUTES};
 
 pub use transaction::{RequestTransactionInfo, Transaction, TransactionStore};
 
@@ -0,0 +1,177 @@
+/*-------------------------------------------------------------------------
+ * Copyright (c) Microsoft Corporation.  All rights reserved.
+ *
+ * src/context/retryable_write.rs
+ *
+ *-------------------------------------------------------------------------
+ */
+
+use std::{collections::HashMap, sync::Arc};
+
+use bson::RawDocumentBuf;
+use tokio::sync::{watch, RwLock};
+
+use crate::error::{DocumentDBError, ErrorCode, Result};
+
+/// Identifies a statement of a retryable write.
+#[derive(Debug, Clone)]
+pub struct RetryableWriteKey {
+    pub session_id: Vec<u8>,
+    pub transaction_number: i64,
+    pub statement_id: i32,
+}
+
+enum StatementState {
+    // Closed once the statement completes, or when its guard is dropped without completing
+    InProgress(watch::Receiver<()>),
+    Completed(RawDocumentBuf),
+}
+
+struct SessionWrites {
+    transaction_number: i64,
+    statements: HashMap<i32, StatementState>,
+}
+
+type SessionWritesMap = Arc<RwLock<HashMap<Vec<u8>, SessionWrites>>>;
+
+pub enum RetryableWrite {
+    /// The statement has not run yet, the guard records its result.
+    Execute(RetryableWriteGuard),
+
+    /// The statement already completed, its result is replayed.
+    Replay(RawDocumentBuf),
+}
+
+// Maps Session Id -> Results of the statements of its latest retryable write
+// A newer transaction number replaces the results, and they are forgotten when the session ends.
+pub struct RetryableWriteStore {
+    sessions: SessionWritesMap,
+}
+
+impl Default for RetryableWriteStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RetryableWriteStore {
+    pub fn new() -> Self {
+        RetryableWriteStore {
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Starts a statement, or returns its result when it already completed.
+    /// A retry of a statement which is still running waits for it to finish first.
+    pub async fn begin(&self, key: RetryableWriteKey) -> Result<RetryableWrite> {
+        loop {
+            let mut done = match self.try_begin(&key).await? {
+                Begin::Started(result) => return Ok(result),
+                Begin::Running(done) => done,
+            };
+            // The guard closes the channel when the statement finishes, then its outcome is checked again
+            let _ = done.changed().await;
+        }
+    }
+
+    async fn try_begin(&self, key: &RetryableWriteKey) -> Result<Begin> {
+        let mut sessions = self.sessions.write().await;
+        let writes = sessions
+            .entry(key.session_id.clone())
+            .or_insert(SessionWrites {
+                transaction_number: key.transaction_number,
+                statements: HashMap::new(),
+            });
+
+        if key.transaction_number < writes.transaction_number {
+            return Err(DocumentDBError::documentdb_error(
+                ErrorCode::TransactionTooOld,
+                format!(
+                    "Retryable write with txnNumber {} is prohibited on session because a newer retryable write with txnNumber {} has already started",
+                    key.transaction_number, writes.transaction_number
+                ),
+            ));
+        }
+        if key.transaction_number > writes.transaction_number {
+            writes.transaction_number = key.transaction_number;
+            writes.statements.clear();
+        }
+
+        match writes.statements.get(&key.statement_id) {
+            Some(StatementState::Completed(response)) => {
+                Ok(Begin::Started(RetryableWrite::Replay(response.clone())))
+            }
+            Some(StatementState::InProgress(done)) if done.has_changed().is_err() => {
+                Err(DocumentDBError::documentdb_error(
+                    ErrorCode::ConflictingOperationInProgress,
+                    format!(
+                        "Retryable write with txnNumber {} was interrupted and may have been applied",
+                        key.transaction_number
+                    ),
+                ))
+            }
+            Some(StatementState::InProgress(done)) => Ok(Begin::Running(done.clone())),
+            None => {
+                let (done_sender, done) = watch::channel(());
+                writes
+                    .statements
+                    .insert(key.statement_id, StatementState::InProgress(done));
+                Ok(Begin::Started(RetryableWrite::Execute(
+                    RetryableWriteGuard {
+                        sessions: self.sessions.clone(),
+                        key: key.clone(),
+                        _done: done_sender,
+                    },
+                )))
+            }
+        }
+    }
+
+    pub async fn remove_session(&self, session_id: &[u8]) {
+        self.sessions.write().await.remove(session_id);
+    }
+}
+
+enum Begin {
+    Started(RetryableWrite),
+    Running(watch::Receiver<()>),
+}
+
+// Records the outcome of a statement, waking the retries waiting for it. A guard dropped before completing leaves
+// the statement in progress, since its write may have been applied: retries get ConflictingOperationInProgress
+// rather than running it twice.
+pub struct RetryableWriteGuard {
+    sessions: SessionWritesMap,
+    key: RetryableWriteKey,
+    _done: watch::Sender<()>,
+}
+
+impl RetryableWriteGuard {
+    /// Keeps the response of a successful statement to replay it on retry, a failed statement may run again.
+    pub async fn complete(self, response: Option<RawDocumentBuf>) {
+        complete_statement(&self.sessions, &self.key, response).await
+    }
+}
+
+async fn complete_statement(
+    sessions: &SessionWritesMap,
+    key: &RetryableWriteKey,
+    response: Option<RawDocumentBuf>,
+) {
+    let mut sessions = sessions.write().await;
+    if let Some(writes) = sessions
+        .get_mut(&key.session_id)
+        .filter(|writes| writes.transaction_number == key.transaction_number)
+    {
+        match response {
+            Some(response) => {
+                writes
+                    .statements
+                    .insert(key.statement_id, StatementState::Completed(response));
+            }
+            None => {
+                writes.statements.remove(&key.statement_id);
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+/*-------------------------------------------------------------------------
+ * Copyright (c) Microsoft Corporation.  All rights reserved.
+ *
+ * src/context/scram_cache.rs
+ *
+ *-------------------------------------------------------------------------
+ */
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use openssl::{hash::MessageDigest, memcmp, pkey::PKey, sign::Signer};
+use tokio::{sync::RwLock, task::JoinHandle};
+
+use crate::{configuration::SetupConfiguration, error::Result};
+
+/// The SCRAM-SHA-256 secret of a user, enough to verify a client proof without the backend.
+#[derive(Debug)]
+pub struct ScramKeys {
+    pub salt: String,
+    pub iterations: i32,
+    stored_key: Vec<u8>,
+    server_key: Vec<u8>,
+}
+
+impl ScramKeys {
+    /// Parses a Postgres SCRAM secret, "SCRAM-SHA-256$<iterations>:<salt>$<StoredKey>:<ServerKey>".
+    pub fn parse(secret: &str) -> Option<Self> {
+        let (iterations, rest) = secret.strip_prefix("SCRAM-SHA-256$")?.split_once(':')?;
+        let (salt, rest) = rest.split_once('$')?;
+        let (stored_key, server_key) = rest.split_once(':')?;
+
+        Some(ScramKeys {
+            salt: salt.to_string(),
+            iterations: iterations.parse().ok()?,
+            stored_key: STANDARD.decode(stored_key).ok()?,
+            server_key: STANDARD.decode(server_key).ok()?,
+        })
+    }
+
+    /// Verifies the client proof for the auth message, returning the base64 server signature when it matches.
+    pub fn verify_proof(&self, auth_message: &str, proof: &str) -> Result<Option<String>> {
+        let Ok(proof) = STANDARD.decode(proof) else {
+            return Ok(None);
+        };
+        let client_signature = hmac(&self.stored_key, auth_message)?;
+        if proof.len() != client_signature.len() {
+            return Ok(None);
+        }
+
+        let client_key: Vec<u8> = proof
+            .iter()
+            .zip(client_signature.iter())
+            .map(|(p, s)| p ^ s)
+            .collect();
+        let stored_key = openssl::sha::sha256(&client_key);
+        if stored_key.len() != self.stored_key.len() || !memcmp::eq(&stored_key, &self.stored_key) {
+            return Ok(None);
+        }
+
+        Ok(Some(STANDARD.encode(hmac(&self.server_key, auth_message)?)))
+    }
+}
+
+fn hmac(key: &[u8], message: &str) -> Result<Vec<u8>> {
+    let key = PKey::hmac(key)?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(message.as_bytes())?;
+    Ok(signer.sign_to_vec()?)
+}
+
+struct ScramCacheEntry {
+    keys: Arc<ScramKeys>,
+    inserted: Instant,
+    generation: u64,
+}
+
+// Caches SCRAM keys per user so that reconnecting clients are verified without a backend round trip.
+// Entries expire after the TTL, and entries loaded under an older dynamic configuration are ignored.
+// The cache is per process: a password change through another gateway is only seen once the entry expires.
+pub struct ScramCache {
+    ttl: Option<Duration>,
+    entries: Arc<RwLock<HashMap<String, ScramCacheEntry>>>,
+    _reaper: Option<JoinHandle<()>>,
+}
+
+impl ScramCache {
+    pub fn new(config: &dyn SetupConfiguration) -> Self {
+        let ttl = match config.scram_cache_ttl_secs() {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+        let entries: Arc<RwLock<HashMap<String, ScramCacheEntry>>> =
+            Arc::new(RwLock::new(HashMap::new()));
+
+        let reaper = ttl.map(|ttl| {
+            let entries_clone = entries.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(ttl.max(Duration::from_secs(60)));
+                loop {
+                    interval.tick().await;
+                    let mut entries = entries_clone.write().await;
+                    entries.retain(|_, v| v.inserted.elapsed() < ttl)
+                }
+            })
+        });
+
+        ScramCache {
+            ttl,
+            entries,
+            _reaper: reaper,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.ttl.is_some()
+    }
+
+    pub async fn get(&self, username: &str, generation: u64) -> Option<Arc<ScramKeys>> {
+        let ttl = self.ttl?;
+        let entries = self.entries.read().await;
+        entries
+            .get(username)
+            .filter(|entry| entry.generation == generation && entry.inserted.elapsed() < ttl)
+            .map(|entry| entry.keys.clone())
+    }
+
+    pub async fn insert(&self, username: &str, keys: Arc<ScramKeys>, generation: u64) {
+        if self.enabled() {
+            self.entries.write().await.insert(
+                username.to_string(),
+                ScramCacheEntry {
+                    keys,
+                    inserted: Instant::now(),
+                    generation,
+                },
+            );
+        }
+    }
+
+    pub async fn invalidate(&self, username: &str) {
+        if self.enabled() {
+            self.entries.write().await.remove(username);
+        }
+    }
+}
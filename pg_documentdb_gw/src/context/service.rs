@@ -6,18 +6,27 @@
  *-------------------------------------------------------------------------
  */
 
-use std::{borrow::Cow, collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::{Arc, Weak},
+    time::Duration,
+};
 
 use tokio::sync::RwLock;
 
 use crate::{
     configuration::{DynamicConfiguration, SetupConfiguration},
     error::{DocumentDBError, Result},
+    oidc::JwksCache,
     postgres::{Connection, ConnectionPool},
     QueryCatalog,
 };
 
-use super::{CursorStore, CursorStoreEntry, TransactionStore};
+use super::{
+    AuthThrottle, CursorStore, CursorStoreEntry, RetryableWriteStore, ScramCache, SessionStore,
+    TransactionStore, LOGICAL_SESSION_TIMEOUT_MINUTES,
+};
 
 type ClientKey = (Cow<'static, str>, Cow<'static, str>);
 
@@ -29,6 +38,11 @@ pub struct ServiceContextInner {
     pub user_data_pools: RwLock<HashMap<ClientKey, ConnectionPool>>,
     pub cursor_store: CursorStore,
     pub transaction_store: TransactionStore,
+    pub session_store: SessionStore,
+    pub retryable_write_store: RetryableWriteStore,
+    pub auth_throttle: AuthThrottle,
+    pub scram_cache: ScramCache,
+    pub jwks_cache: JwksCache,
     pub query_catalog: QueryCatalog,
 }
 
@@ -54,9 +68,51 @@ impl ServiceContext {
             user_data_pools: RwLock::new(HashMap::new()),
             cursor_store: CursorStore::new(setup_configuration.as_ref(), true),
             transaction_store: TransactionStore::new(Duration::from_secs(timeout_secs)),
+            session_store: SessionStore::new(Duration::from_secs(
+                LOGICAL_SESSION_TIMEOUT_MINUTES * 60,
+            )),
+            retryable_write_store: RetryableWriteStore::new(),
+            auth_throttle: AuthThrottle::new(setup_configuration.as_ref()),
+            scram_cache: ScramCache::new(setup_configuration.as_ref()),
+            jwks_cache: JwksCache::new(),
             query_catalog,
         };
-        Ok(ServiceContext(Arc::new(inner)))
+        let inner = Arc::new(inner);
+        Self::start_session_reaper(Arc::downgrade(&inner));
+        Ok(ServiceContext(inner))
+    }
+
+    // Ends the sessions which stayed idle for longer than the logical session timeout
+    fn start_session_reaper(inner: Weak<ServiceContextInner>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                let Some(inner) = inner.upgrade() else {
+                    break;
+                };
+                let service_context = ServiceContext(inner);
+                for session_id in service_context.session_store().remove_expired().await {
+                    log::debug!("Logical session {:?} expired", session_id);
+                    service_context.clean_up_session(&session_id).await;
+                }
+            }
+        });
+    }
+
+    /// Ends a logical session, aborting its transaction, killing its cursors and forgetting its retryable writes.
+    pub async fn end_session(&self, session_id: &[u8]) {
+        self.0.session_store.remove(session_id).await;
+        self.clean_up_session(session_id).await
+    }
+
+    async fn clean_up_session(&self, session_id: &[u8]) {
+        self.0.transaction_store.remove_session(session_id).await;
+        self.0
+            .retryable_write_store
+            .remove_session(session_id)
+            .await;
+        self.invalidate_cursors_by_session(session_id).await
     }
 
     pub async fn get_data_conn(&'_ self, user: &str, pass: &str) -> Result<Connection> {
@@ -132,6 +188,26 @@ impl ServiceContext {
         &self.0.transaction_store
     }
 
+    pub fn session_store(&self) -> &SessionStore {
+        &self.0.session_store
+    }
+
+    pub fn retryable_write_store(&self) -> &RetryableWriteStore {
+        &self.0.retryable_write_store
+    }
+
+    pub fn auth_throttle(&self) -> &AuthThrottle {
+        &self.0.auth_throttle
+    }
+
+    pub fn scram_cache(&self) -> &ScramCache {
+        &self.0.scram_cache
+    }
+
+    pub fn jwks_cache(&self) -> &JwksCache {
+        &self.0.jwks_cache
+    }
+
     pub fn query_catalog(&self) -> &QueryCatalog {
         &self.0.query_catalog
     }
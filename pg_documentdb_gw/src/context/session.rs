@@ -0,0 +1,113 @@
+/*-------------------------------------------------------------------------
+ * Copyright (c) Microsoft Corporation.  All rights reserved.
+ *
+ * src/context/session.rs
+ *
+ *-------------------------------------------------------------------------
+ */
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::RwLock;
+
+use crate::error::{DocumentDBError, Result};
+
+/// How long a logical session may stay idle before it expires, advertised to clients by hello.
+pub const LOGICAL_SESSION_TIMEOUT_MINUTES: u64 = 30;
+
+#[derive(Debug)]
+pub struct Session {
+    pub owner: String,
+    pub last_use: Instant,
+}
+
+// Maps Session Id -> Owner, Last use
+// The transactions and cursors of a session are found by its id in their own stores.
+pub struct SessionStore {
+    sessions: RwLock<HashMap<Vec<u8>, Session>>,
+    timeout: Duration,
+}
+
+impl SessionStore {
+    pub fn new(timeout: Duration) -> Self {
+        SessionStore {
+            sessions: RwLock::new(HashMap::new()),
+            timeout,
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Records a use of the session, starting it for the user if it is not known yet.
+    /// Fails when the session belongs to another user.
+    pub async fn touch(&self, session_id: &[u8], owner: &str) -> Result<()> {
+        let mut sessions = self.sessions.write().await;
+        match sessions.get_mut(session_id) {
+            Some(session) => {
+                check_owner(session, owner)?;
+                session.last_use = Instant::now()
+            }
+            None => {
+                sessions.insert(
+                    session_id.to_vec(),
+                    Session {
+                        owner: owner.to_string(),
+                        last_use: Instant::now(),
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails when the session is known and belongs to another user.
+    pub async fn check_owner(&self, session_id: &[u8], user: &str) -> Result<()> {
+        match self.sessions.read().await.get(session_id) {
+            Some(session) => check_owner(session, user),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn remove(&self, session_id: &[u8]) -> Option<Session> {
+        self.sessions.write().await.remove(session_id)
+    }
+
+    /// Returns the ids of the sessions owned by any of the users, or of all sessions without users.
+    pub async fn session_ids(&self, owners: &[&str]) -> Vec<Vec<u8>> {
+        self.sessions
+            .read()
+            .await
+            .iter()
+            .filter(|(_, session)| owners.is_empty() || owners.contains(&session.owner.as_str()))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Removes the sessions which have been idle for longer than the timeout, returning their ids.
+    pub async fn remove_expired(&self) -> Vec<Vec<u8>> {
+        let mut expired = Vec::new();
+        self.sessions.write().await.retain(|id, session| {
+            if session.last_use.elapsed() < self.timeout {
+                true
+            } else {
+                expired.push(id.clone());
+                false
+            }
+        });
+        expired
+    }
+}
+
+fn check_owner(session: &Session, user: &str) -> Result<()> {
+    if session.owner != user {
+        return Err(DocumentDBError::unauthorized(
+            "Cannot use a session owned by another user".to_string(),
+        ));
+    }
+    Ok(())
+}
@@ -33,6 +33,8 @@ pub struct RequestTransactionInfo {
 
 pub struct Transaction {
     pub session_id: Vec<u8>,
+    // The user which started the transaction, the only one allowed to use its connection
+    pub owner: String,
     pub transaction_number: i64,
     pub cursors: CursorStore,
     transaction: Option<postgres::Transaction>,
@@ -45,9 +47,11 @@ impl Transaction {
         conn: Arc<Connection>,
         isolation_level: IsolationLevel,
         session_id: Vec<u8>,
+        owner: String,
     ) -> Result<Self> {
         Ok(Transaction {
             session_id,
+            owner,
             transaction_number: request.transaction_number,
             transaction: Some(postgres::Transaction::start(conn, isolation_level).await?),
             cursors: CursorStore::new(config, false),
@@ -87,6 +91,15 @@ impl Transaction {
     pub fn transaction_number(&self) -> i64 {
         self.transaction_number
     }
+
+    pub fn check_owner(&self, user: &str) -> Result<()> {
+        if self.owner != user {
+            return Err(DocumentDBError::unauthorized(
+                "Cannot use a transaction started by another user".to_string(),
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl Drop for Transaction {
@@ -154,12 +167,18 @@ impl TransactionStore {
         }
     }
 
-    pub async fn get_connection(&self, session_id: &[u8]) -> Option<Arc<Connection>> {
-        self.transactions
-            .read()
-            .await
-            .get(session_id)
-            .and_then(|(_, t)| t.get_connection())
+    pub async fn get_connection(
+        &self,
+        session_id: &[u8],
+        user: &str,
+    ) -> Result<Option<Arc<Connection>>> {
+        match self.transactions.read().await.get(session_id) {
+            Some((_, t)) => {
+                t.check_owner(user)?;
+                Ok(t.get_connection())
+            }
+            None => Ok(None),
+        }
     }
 
     pub async fn create(
@@ -168,6 +187,11 @@ impl TransactionStore {
         transaction_info: &RequestTransactionInfo,
         session_id: Vec<u8>,
     ) -> Result<()> {
+        let user = context.auth_state.username()?;
+        if let Some((_, transaction)) = self.transactions.read().await.get(&session_id) {
+            transaction.check_owner(user)?;
+        }
+
         if let Some((_, transaction_number)) = context.transaction.as_ref() {
             if transaction_number > &transaction_info.transaction_number {
                 return Err(DocumentDBError::documentdb_error(
@@ -235,6 +259,7 @@ impl TransactionStore {
                     .isolation_level
                     .unwrap_or(IsolationLevel::ReadCommitted),
                 session_id.clone(),
+                user.to_string(),
             )
             .await?;
 
@@ -314,6 +339,17 @@ impl TransactionStore {
         }
     }
 
+    /// Aborts any transaction of an ended session and forgets its transaction numbers.
+    pub async fn remove_session(&self, session_id: &[u8]) {
+        let transaction = self.transactions.write().await.remove(session_id);
+        if let Some((_, mut t)) = transaction {
+            if let Err(e) = t.abort().await {
+                log::warn!("Failed to abort the transaction of an ended session: {}", e);
+            }
+        }
+        self.last_seen_transactions.write().await.remove(session_id);
+    }
+
     pub async fn commit(&self, session_id: &[u8]) -> Result<()> {
         if let Some((_, mut t)) = self.transactions.write().await.remove(session_id) {
             t.commit().await?;
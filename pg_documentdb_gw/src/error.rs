@@ -58,6 +58,10 @@ impl DocumentDBError {
         DocumentDBError::DocumentDBError(ErrorCode::InternalError, msg, Backtrace::capture())
     }
 
+    pub fn protocol_error(msg: String) -> Self {
+        DocumentDBError::DocumentDBError(ErrorCode::ProtocolError, msg, Backtrace::capture())
+    }
+
     pub fn type_mismatch(msg: String) -> Self {
         DocumentDBError::DocumentDBError(ErrorCode::TypeMismatch, msg, Backtrace::capture())
     }
@@ -157,6 +161,7 @@ pub enum ErrorCode {
     BadValue = 2,
     Unauthorized = 13,
     TypeMismatch = 14,
+    ProtocolError = 17,
     AuthenticationFailed = 18,
     IllegalOperation = 20,
     LockTimeout = 24,
@@ -175,7 +180,11 @@ pub enum ErrorCode {
     NoSuchTransaction = 251,
     TransactionCommitted = 256,
     OperationNotSupportedInTransaction = 263,
+    ChecksumMismatch = 288,
+    MechanismUnavailable = 334,
+    ReauthenticationRequired = 391,
     NotWritablePrimary = 10107,
+    BSONObjectTooLarge = 10334,
     DuplicateKey = 11000,
     OutOfDiskSpace = 14031,
     UnknownBsonField = 40415,
@@ -189,6 +198,7 @@ impl FromPrimitive for ErrorCode {
             2 => Some(ErrorCode::BadValue),
             13 => Some(ErrorCode::Unauthorized),
             14 => Some(ErrorCode::TypeMismatch),
+            17 => Some(ErrorCode::ProtocolError),
             18 => Some(ErrorCode::AuthenticationFailed),
             20 => Some(ErrorCode::IllegalOperation),
             24 => Some(ErrorCode::LockTimeout),
@@ -207,7 +217,11 @@ impl FromPrimitive for ErrorCode {
             251 => Some(ErrorCode::NoSuchTransaction),
             256 => Some(ErrorCode::TransactionCommitted),
             263 => Some(ErrorCode::OperationNotSupportedInTransaction),
+            288 => Some(ErrorCode::ChecksumMismatch),
+            334 => Some(ErrorCode::MechanismUnavailable),
+            391 => Some(ErrorCode::ReauthenticationRequired),
             10107 => Some(ErrorCode::NotWritablePrimary),
+            10334 => Some(ErrorCode::BSONObjectTooLarge),
             11000 => Some(ErrorCode::DuplicateKey),
             14031 => Some(ErrorCode::OutOfDiskSpace),
             40415 => Some(ErrorCode::UnknownBsonField),
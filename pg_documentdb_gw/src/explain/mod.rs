@@ -16,12 +16,12 @@ use log::{log_enabled, warn};
 use model::*;
 use once_cell::sync::Lazy;
 use serde_json::Value;
-use tokio_postgres::types::Type;
+use tokio_postgres::{types::Type, Row};
 
 use crate::{
     context::ConnectionContext,
     error::{DocumentDBError, Result},
-    postgres::{PgDocument, Timeout},
+    postgres::{Connection, PgDocument, Timeout},
     protocol::OK_SUCCEEDED,
     requests::{Request, RequestInfo, RequestType},
     responses::{RawResponse, Response},
@@ -210,22 +210,17 @@ async fn run_explain(
         let mut pg = connection_context
             .pull_connection_without_transaction(false)
             .await?;
-        let t = pg.get_inner().transaction().await?;
-        let explain_config_query = connection_context
-            .service_context
-            .query_catalog()
-            .set_explain_all_tasks_true();
-        if !explain_config_query.is_empty() {
-            t.batch_execute(explain_config_query).await?;
-        }
-        let stmt = t
-            .prepare_typed_cached(&query, &[Type::TEXT, Type::BYTEA])
-            .await?;
-        t.query(
-            &stmt,
-            &[&request_info.db()?, &PgDocument(request.document())],
+        let guard = pg.cancel_on_drop();
+        let results = run_explain_all_tasks(
+            &mut pg,
+            &query,
+            request_info.db()?,
+            request,
+            connection_context,
         )
-        .await?
+        .await;
+        guard.complete();
+        results?
     } else {
         connection_context
             .pull_connection()
@@ -305,6 +300,29 @@ async fn run_explain(
     }
 }
 
+// Runs the explain in its own transaction, with the backend set up to explain the tasks of all shards
+async fn run_explain_all_tasks(
+    pg: &mut Connection,
+    query: &str,
+    db: &str,
+    request: &Request<'_>,
+    connection_context: &ConnectionContext,
+) -> Result<Vec<Row>> {
+    let t = pg.get_inner().transaction().await?;
+    let explain_config_query = connection_context
+        .service_context
+        .query_catalog()
+        .set_explain_all_tasks_true();
+    if !explain_config_query.is_empty() {
+        t.batch_execute(explain_config_query).await?;
+    }
+    let stmt = t
+        .prepare_typed_cached(query, &[Type::TEXT, Type::BYTEA])
+        .await?;
+    Ok(t.query(&stmt, &[&db, &PgDocument(request.document())])
+        .await?)
+}
+
 fn developer_explain(
     query: &str,
     explain_content: RawBson,
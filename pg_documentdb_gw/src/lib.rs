@@ -11,18 +11,27 @@
 use configuration::{CertificateOptions, DynamicConfiguration, SetupConfiguration};
 use either::Either::{Left, Right};
 use error::ErrorCode;
+use ipnet::IpNet;
 use log::{error, log_enabled, warn};
-use openssl::ssl::{Ssl, SslContextBuilder, SslMethod, SslOptions};
+use openssl::{ssl::Ssl, x509::X509VerifyResult};
 use protocol::header::Header;
+use protocol::message::MessageFlags;
+use protocol::opcode::OpCode;
 use requests::request_tracker::RequestTracker;
 use requests::{Request, RequestInfo, RequestIntervalKind, RequestMessage};
 use responses::{CommandError, Response};
 use socket2::TcpKeepalive;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
 use std::sync::Arc;
-use std::{net::SocketAddr, pin::Pin, time::Duration};
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::Path,
+    pin::Pin,
+    time::Duration,
+};
 use telemetry::TelemetryProvider;
-use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, UnixListener};
 use tokio_openssl::SslStream;
 use tokio_util::sync::CancellationToken;
 
@@ -30,6 +39,7 @@ use crate::context::{ConnectionContext, ServiceContext};
 use crate::error::{DocumentDBError, Result};
 use crate::postgres::ConnectionPool;
 use crate::requests::RequestType;
+use crate::tls::TlsProvider;
 
 pub use crate::postgres::QueryCatalog;
 
@@ -39,15 +49,28 @@ pub mod configuration;
 pub mod context;
 pub mod error;
 pub mod explain;
+pub mod oidc;
 pub mod postgres;
 pub mod processor;
 pub mod protocol;
 pub mod requests;
 pub mod responses;
 pub mod telemetry;
+pub mod tls;
 
 pub const SYSTEM_REQUESTS_MAX_CONNECTIONS: usize = 2;
 pub const AUTHENTICATION_MAX_CONNECTIONS: usize = 5;
+const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+const TLS_DETECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How TLS is applied to connections on the TCP listener
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TlsMode {
+    Required,
+    Disabled,
+    // Each connection is served with TLS only if it starts with a TLS handshake
+    Detect,
+}
 
 pub async fn run_server(
     sc: ServiceContext,
@@ -67,16 +90,66 @@ pub async fn run_server(
     ))
     .await?;
 
-    let enforce_ssl_tcp = sc.setup_configuration().enforce_ssl_tcp();
+    if let Some(path) = sc.setup_configuration().unix_socket_path() {
+        let unix_listener = bind_unix_socket(
+            &path,
+            sc.setup_configuration().unix_socket_file_permissions(),
+        )
+        .await?;
+        let (sc, telemetry, token) = (sc.clone(), telemetry.clone(), token.clone());
+        tokio::spawn(async move {
+            // Listen for new unix socket connections until cancelled
+            loop {
+                match listen_for_unix_connections(
+                    sc.clone(),
+                    telemetry.clone(),
+                    &unix_listener,
+                    token.clone(),
+                )
+                .await
+                {
+                    Err(e) => error!("[], Failed to accept a unix socket connection: {:?}", e),
+                    Ok(true) => continue,
+                    Ok(false) => break,
+                }
+            }
+            if let Err(e) = tokio::fs::remove_file(&path).await {
+                warn!("Failed to remove unix socket {}: {}", path, e);
+            }
+        });
+    }
+
+    let tls_provider = TlsProvider::new(
+        certificate_options,
+        sc.setup_configuration().certificate_refresh_interval_secs(),
+        token.clone(),
+    )
+    .await?;
+
+    let tls_mode = if sc.setup_configuration().detect_ssl_tcp() {
+        TlsMode::Detect
+    } else if sc.setup_configuration().enforce_ssl_tcp() {
+        TlsMode::Required
+    } else {
+        TlsMode::Disabled
+    };
+    let trusted_proxies: Option<Arc<[IpNet]>> = if sc.setup_configuration().enable_proxy_protocol()
+    {
+        Some(sc.setup_configuration().trusted_proxy_cidrs().into())
+    } else {
+        None
+    };
+
     // Listen for new tcp connections until cancelled
     loop {
         match listen_for_connections(
             sc.clone(),
-            &certificate_options,
+            &tls_provider,
             telemetry.clone(),
             &listener,
             token.clone(),
-            enforce_ssl_tcp,
+            tls_mode,
+            trusted_proxies.clone(),
             cipher_map,
         )
         .await
@@ -128,7 +201,7 @@ pub async fn populate_ssl_certificates() -> Result<CertificateOptions> {
         cert_type: "pem_file".to_string(),
         file_path: "./cert.pem".to_string(),
         key_file_path: "./key.pem".to_string(),
-        ca_path: None,
+        ..Default::default()
     })
 }
 
@@ -139,27 +212,17 @@ pub async fn populate_ssl_certificates() -> Result<CertificateOptions> {
 
 async fn listen_for_connections(
     sc: ServiceContext,
-    certificate_options: &CertificateOptions,
+    tls_provider: &TlsProvider,
     telemetry: Option<Box<dyn TelemetryProvider>>,
     listener: &TcpListener,
     token: CancellationToken,
-    enforce_ssl_tcp: bool,
+    tls_mode: TlsMode,
+    trusted_proxies: Option<Arc<[IpNet]>>,
     cipher_map: Option<fn(Option<&str>) -> i32>,
 ) -> Result<bool> {
     let token_clone = token.clone();
 
-    let mut ctx = SslContextBuilder::new(SslMethod::tls()).unwrap();
-    ctx.set_private_key_file(
-        &certificate_options.key_file_path,
-        openssl::ssl::SslFiletype::PEM,
-    )?;
-    ctx.set_certificate_chain_file(&certificate_options.file_path)?;
-    if let Some(ca_path) = certificate_options.ca_path.as_deref() {
-        ctx.set_ca_file(ca_path)?;
-    }
-    ctx.set_options(SslOptions::NO_TLSV1 | SslOptions::NO_TLSV1_1);
-
-    let ssl = Ssl::new(&ctx.build())?;
+    let ssl = tls_provider.ssl()?;
     // Wait for either a new connection or cancellation
     tokio::select! {
         res = listener.accept() => {
@@ -171,7 +234,91 @@ async fn listen_for_connections(
             socket2::SockRef::from(&stream).set_tcp_keepalive(&TcpKeepalive::new().with_interval(Duration::from_secs(60)).with_time(Duration::from_secs(180)))?;
             tokio::spawn(async move {
                 tokio::select! {
-                    _ = handle_connection(ssl, sc, telemetry, ip, stream, enforce_ssl_tcp, cipher_map) => {}
+                    _ = handle_connection(ssl, sc, telemetry, ip, stream, tls_mode, trusted_proxies, cipher_map) => {}
+                    _ = token_clone.cancelled() => {}
+                }
+            });
+            Ok(true)
+        }
+        _ = token_clone.cancelled() => {
+            Ok(false)
+        }
+    }
+}
+
+/// Binds the Unix domain socket, replacing a stale socket file left behind by a previous process.
+async fn bind_unix_socket(path: &str, permissions: u32) -> Result<UnixListener> {
+    match tokio::fs::symlink_metadata(path).await {
+        Ok(metadata) if metadata.file_type().is_socket() => tokio::fs::remove_file(path).await?,
+        Ok(_) => {
+            return Err(DocumentDBError::internal_error(format!(
+                "Unix socket path {} exists and is not a socket",
+                path
+            )))
+        }
+        Err(_) => {}
+    }
+
+    // Bind inside a directory only the gateway can enter and move the socket into place once its permissions
+    // are set, so other local users can never connect through umask-default permissions.
+    let path = Path::new(path);
+    let file_name = path
+        .file_name()
+        .ok_or(DocumentDBError::internal_error(format!(
+            "Unix socket path {} has no file name",
+            path.display()
+        )))?
+        .to_string_lossy();
+    let staging = path.with_file_name(format!(".{}.{}", file_name, std::process::id()));
+    tokio::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&staging)
+        .await?;
+
+    let staged = staging.join("s");
+    let result: Result<UnixListener> = async {
+        let listener = UnixListener::bind(&staged)?;
+        tokio::fs::set_permissions(&staged, std::fs::Permissions::from_mode(permissions)).await?;
+        tokio::fs::rename(&staged, path).await?;
+        Ok(listener)
+    }
+    .await;
+
+    if let Err(e) = tokio::fs::remove_dir_all(&staging).await {
+        warn!("Failed to remove {}: {}", staging.display(), e);
+    }
+    result
+}
+
+async fn listen_for_unix_connections(
+    sc: ServiceContext,
+    telemetry: Option<Box<dyn TelemetryProvider>>,
+    listener: &UnixListener,
+    token: CancellationToken,
+) -> Result<bool> {
+    let token_clone = token.clone();
+
+    // Wait for either a new connection or cancellation
+    tokio::select! {
+        res = listener.accept() => {
+            // Local connections are not encrypted, access is controlled by the socket's file permissions
+            let (stream, _) = res?;
+            log::trace!("New unix socket connection established.");
+
+            tokio::spawn(async move {
+                let mut connection_context = ConnectionContext::new(
+                    sc,
+                    telemetry,
+                    SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+                    String::new(),
+                )
+                .await;
+                match stream.peer_cred() {
+                    Ok(credentials) => connection_context.unix_peer_uid = Some(credentials.uid()),
+                    Err(e) => warn!("Failed to read unix socket peer credentials: {}", e),
+                }
+                tokio::select! {
+                    _ = handle_stream(stream, connection_context) => {}
                     _ = token_clone.cancelled() => {}
                 }
             });
@@ -194,16 +341,68 @@ async fn handle_connection(
     sc: ServiceContext,
     telemetry: Option<Box<dyn TelemetryProvider>>,
     ip: SocketAddr,
-    stream: TcpStream,
-    enforce_ssl_tcp: bool,
+    mut stream: TcpStream,
+    tls_mode: TlsMode,
+    trusted_proxies: Option<Arc<[IpNet]>>,
     cipher_map: Option<fn(Option<&str>) -> i32>,
 ) {
+    // Connections from trusted proxies carry the client's address in a PROXY header ahead of the TLS handshake
+    let ip = match trusted_proxies {
+        Some(trusted) => {
+            match protocol::proxy::resolve_client_address(
+                &mut stream,
+                ip,
+                &trusted,
+                PROXY_HEADER_TIMEOUT,
+            )
+            .await
+            {
+                Ok(source) => source,
+                Err(e) => {
+                    log::error!("Failed to read PROXY header from {}: {}", ip, e);
+                    return;
+                }
+            }
+        }
+        None => ip,
+    };
+
+    let use_tls = match tls_mode {
+        TlsMode::Required => true,
+        TlsMode::Disabled => false,
+        TlsMode::Detect => {
+            match tokio::time::timeout(TLS_DETECTION_TIMEOUT, starts_with_tls_handshake(&stream))
+                .await
+            {
+                Ok(Ok(use_tls)) => use_tls,
+                Ok(Err(e)) => {
+                    log::error!("Failed to detect the transport of a connection: {}", e);
+                    return;
+                }
+                Err(_) => {
+                    log::error!(
+                        "Timed out detecting the transport of a connection from {}",
+                        ip
+                    );
+                    return;
+                }
+            }
+        }
+    };
+
     let mut connection_context =
         ConnectionContext::new(sc, telemetry, ip, ssl.version_str().to_string()).await;
+    connection_context.tls = use_tls;
 
-    match enforce_ssl_tcp {
+    match use_tls {
         true => match get_stream(ssl, stream).await {
             Ok(stream) => {
+                connection_context.ssl_protocol = stream.ssl().version_str().to_string();
+
+                // Only certificates whose chain verified against the CA are kept for X.509 authentication
+                if stream.ssl().verify_result() == X509VerifyResult::OK {
+                    connection_context.client_certificate = stream.ssl().peer_certificate();
+                }
                 connection_context.cipher_type = match cipher_map {
                     Some(map) => map(stream.ssl().current_cipher().map(|cipher| cipher.name())),
                     None => 0,
@@ -216,14 +415,51 @@ async fn handle_connection(
     }
 }
 
-async fn handle_stream<R>(mut stream: R, mut connection_context: ConnectionContext)
+/// Peeks at the first bytes of the stream, without consuming them, to check for a TLS handshake record.
+/// The record header is content type 22, a TLS 1.x record version and a big endian length of at most 2^14 bytes.
+/// An OP_MSG starts with its little endian length instead, so a plaintext message could only match when it is one
+/// of a few exact lengths over 64KB. The first message of a connection is the small hello handshake, which never does.
+async fn starts_with_tls_handshake(stream: &TcpStream) -> Result<bool> {
+    const TLS_HANDSHAKE_CONTENT_TYPE: u8 = 0x16;
+    const TLS_MAJOR_VERSION: u8 = 0x03;
+    const TLS_MAX_RECORD_LENGTH: u16 = 1 << 14;
+
+    let mut buf = [0_u8; 5];
+    loop {
+        let read = stream.peek(&mut buf).await?;
+        if read == 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        if read == buf.len() {
+            break;
+        }
+
+        // Wait for the rest of the bytes rather than spinning on the partially filled socket
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+
+    let record_length = u16::from_be_bytes([buf[3], buf[4]]);
+    Ok(buf[0] == TLS_HANDSHAKE_CONTENT_TYPE
+        && buf[1] == TLS_MAJOR_VERSION
+        && (0x01..=0x04).contains(&buf[2])
+        && (1..=TLS_MAX_RECORD_LENGTH).contains(&record_length))
+}
+
+async fn handle_stream<R>(stream: R, mut connection_context: ConnectionContext)
 where
     R: AsyncRead + AsyncWrite + Unpin + Send,
 {
+    // Buffered so that a disconnect can be noticed during a request without consuming pipelined requests
+    let mut stream = BufReader::new(stream);
     loop {
         match protocol::reader::read_header(&mut stream).await {
-            Ok(Some(header)) => {
-                if let Err(e) = handle_message(&mut connection_context, &header, &mut stream).await
+            Ok(Some(mut header)) => {
+                let setup_configuration = connection_context.service_context.setup_configuration();
+                header.response_checksum = setup_configuration.enable_response_checksums();
+                header.response_document_sequences =
+                    setup_configuration.enable_response_document_sequences();
+                if let Err(e) =
+                    handle_message(&mut connection_context, &mut header, &mut stream).await
                 {
                     if let Err(e) = log_and_write_error(
                         &connection_context,
@@ -248,6 +484,7 @@ where
             }
 
             // Failed to read a header, can't provide request id in the error so use connection id instead.
+            // The stream can't be resynchronized after a bad header, so the connection is closed.
             Err(e) => {
                 if let Err(e) = responses::writer::write_error_without_header(
                     &connection_context,
@@ -260,8 +497,8 @@ where
                         "[C:{}] Couldn't reply with error {:?}",
                         connection_context.connection_id, e
                     );
-                    break;
                 }
+                break;
             }
         }
     }
@@ -289,6 +526,7 @@ async fn get_response(
         }
     }
 
+    auth::check_expiry(ctx)?;
     if !ctx.auth_state.authorized || request.request_type().handle_with_auth() {
         let response = auth::process(ctx, request).await?;
         return Ok(response);
@@ -314,11 +552,11 @@ async fn get_response(
 
 async fn handle_message<R>(
     connection_context: &mut ConnectionContext,
-    header: &Header,
+    header: &mut Header,
     stream: &mut R,
 ) -> Result<()>
 where
-    R: AsyncRead + AsyncWrite + Unpin + Send,
+    R: AsyncBufRead + AsyncWrite + Unpin + Send,
 {
     // Read the request message off the stream
     let mut request_tracker = RequestTracker::new();
@@ -420,19 +658,35 @@ async fn handle_request<R>(
     request_info: &mut RequestInfo<'_>,
 ) -> Result<()>
 where
-    R: AsyncRead + AsyncWrite + Unpin + Send,
+    R: AsyncBufRead + AsyncWrite + Unpin + Send,
 {
     *collection = request_info.collection().unwrap_or("").to_string();
 
-    // Process the response for the message
-    let response = get_response(ctx, message, request, request_info, header).await?;
+    // Process the response for the message, dropping the request and cancelling its queries if the client goes away.
+    // Retryable writes run to completion instead, so that a retry from the reconnected client replays their result.
+    let abandon_on_disconnect = processor::retryable_write_key(request, request_info).is_none();
+    let response = tokio::select! {
+        response = get_response(ctx, message, request, request_info, header) => response?,
+        _ = client_disconnected(stream), if abandon_on_disconnect => {
+            log::debug!(activity_id = header.activity_id.as_str(); "[{}] Client disconnected, request abandoned", header.request_id);
+            return Ok(());
+        }
+    };
 
     let format_response_start = request_info.request_tracker.start_timer();
 
     // Write the response back to the stream
     if ctx.requires_response {
-        responses::writer::write(header, &response, stream).await?;
-        stream.flush().await?;
+        if ctx.legacy_cursor_reply {
+            write_legacy_reply(ctx, header, request, &response, stream).await?;
+        } else if ctx.exhaust_allowed
+            && header.op_code == OpCode::Msg
+            && request.request_type() == &RequestType::GetMore
+        {
+            write_exhaust(ctx, header, request, request_info, &response, stream).await?;
+        } else {
+            responses::writer::write(header, &response, stream).await?;
+        }
     }
 
     if let Some(telemetry) = ctx.telemetry_provider.as_ref() {
@@ -455,6 +709,89 @@ where
     Ok(())
 }
 
+// Resolves once the client closes the connection. Bytes of a pipelined request are left in the buffer,
+// after which the disconnect is only noticed by the next read.
+async fn client_disconnected<R>(stream: &mut R)
+where
+    R: AsyncBufRead + Unpin,
+{
+    match stream.fill_buf().await {
+        Ok(buf) if !buf.is_empty() => std::future::pending().await,
+        _ => {}
+    }
+}
+
+// Replies to legacy OP_QUERY and OP_GET_MORE with the batch as OP_REPLY documents,
+// tracking the cursor position across batches for startingFrom.
+async fn write_legacy_reply<R>(
+    ctx: &mut ConnectionContext,
+    header: &Header,
+    request: &Request<'_>,
+    response: &Response,
+    stream: &mut R,
+) -> Result<()>
+where
+    R: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    let starting_from = match request.document().get_i64("getMore") {
+        Ok(cursor_id) => ctx.take_legacy_cursor_position(cursor_id),
+        Err(_) => 0,
+    };
+
+    let (cursor_id, number_returned) = responses::writer::write_legacy_reply(
+        header,
+        response.as_raw_document()?,
+        starting_from,
+        stream,
+    )
+    .await?;
+
+    if cursor_id != 0 {
+        ctx.set_legacy_cursor_position(cursor_id, starting_from + number_returned);
+    }
+    Ok(())
+}
+
+// Streams getMore batches with moreToCome set until the cursor is exhausted.
+// Each batch is issued with the original getMore request, without waiting on the client.
+async fn write_exhaust<R>(
+    ctx: &ConnectionContext,
+    header: &Header,
+    request: &Request<'_>,
+    request_info: &mut RequestInfo<'_>,
+    first_response: &Response,
+    stream: &mut R,
+) -> Result<()>
+where
+    R: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    let mut next_response = None;
+    loop {
+        let response = next_response.as_ref().unwrap_or(first_response);
+        let more_to_come = processor::cursor_id(response)? != 0;
+        let flags = if more_to_come {
+            MessageFlags::MORE_TO_COME
+        } else {
+            MessageFlags::empty()
+        };
+
+        responses::writer::write_message_with_flags(
+            header,
+            response.as_raw_document()?,
+            flags,
+            stream,
+        )
+        .await?;
+        stream.flush().await?;
+
+        if !more_to_come {
+            return Ok(());
+        }
+
+        next_response = Some(processor::process_get_more(request, request_info, ctx).await?);
+    }
+}
+
 async fn log_and_write_error<R>(
     connection_context: &ConnectionContext,
     header: &Header,
@@ -467,10 +804,17 @@ async fn log_and_write_error<R>(
 where
     R: AsyncRead + AsyncWrite + Unpin + Send,
 {
-    let error_response = CommandError::from_error(connection_context, e).await;
+    let mut error_response = CommandError::from_error(connection_context, e).await;
+    if request.is_some_and(|request| request.is_retryable_write()) {
+        error_response.add_retryable_write_label();
+    }
     let response = error_response.to_raw_document_buf()?;
 
-    responses::writer::write_response(header, &response, stream).await?;
+    if connection_context.legacy_cursor_reply {
+        responses::writer::write_legacy_error(header, &error_response, stream).await?;
+    } else {
+        responses::writer::write_response(header, &response, stream).await?;
+    }
 
     log::error!(activity_id = header.activity_id.as_str(); "Request failure: {e}");
 
@@ -0,0 +1,174 @@
+/*-------------------------------------------------------------------------
+ * Copyright (c) Microsoft Corporation.  All rights reserved.
+ *
+ * src/oidc.rs
+ *
+ *-------------------------------------------------------------------------
+ */
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use jsonwebtoken::{
+    jwk::{AlgorithmParameters, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use serde_json::{Map, Value};
+use tokio::sync::RwLock;
+
+use crate::{
+    configuration::OidcOptions,
+    error::{DocumentDBError, ErrorCode, Result},
+};
+
+/// The user authenticated by a bearer token.
+#[derive(Debug)]
+pub struct OidcIdentity {
+    /// The Postgres role named by the principal claim.
+    pub username: String,
+
+    /// When the token expires, after which the connection has to authenticate again.
+    pub expires_at: SystemTime,
+}
+
+/// The identity provider's signing keys, read from the JWKS file on first use and re-read once they are older
+/// than the refresh interval, so that rotated keys are picked up without a restart.
+/// The file is read with tokio::fs, off the executor threads.
+#[derive(Default)]
+pub struct JwksCache {
+    key_set: RwLock<Option<(Arc<JwkSet>, Instant)>>,
+}
+
+impl JwksCache {
+    pub fn new() -> Self {
+        JwksCache::default()
+    }
+
+    /// Returns the cached key set, reading the file when it was never read or is due for a refresh.
+    /// A failed refresh keeps the previous keys until the next interval.
+    pub async fn get(&self, options: &OidcOptions) -> Result<Arc<JwkSet>> {
+        let refresh_interval = options.jwks_refresh_interval();
+        if let Some((key_set, loaded_at)) = self.key_set.read().await.as_ref() {
+            if loaded_at.elapsed() < refresh_interval {
+                return Ok(key_set.clone());
+            }
+        }
+
+        let mut cached = self.key_set.write().await;
+
+        // Another authentication may have refreshed the keys while this one waited for the lock
+        if let Some((key_set, loaded_at)) = cached.as_ref() {
+            if loaded_at.elapsed() < refresh_interval {
+                return Ok(key_set.clone());
+            }
+        }
+
+        match read_key_set(&options.jwks_file_path).await {
+            Ok(key_set) => {
+                let key_set = Arc::new(key_set);
+                *cached = Some((key_set.clone(), Instant::now()));
+                Ok(key_set)
+            }
+            Err(e) => match cached.as_mut() {
+                Some((key_set, loaded_at)) => {
+                    log::warn!(
+                        "Failed to refresh the JWKS, keeping the previous keys: {}",
+                        e
+                    );
+                    *loaded_at = Instant::now();
+                    Ok(key_set.clone())
+                }
+                None => Err(e),
+            },
+        }
+    }
+}
+
+async fn read_key_set(path: &str) -> Result<JwkSet> {
+    serde_json::from_slice(&tokio::fs::read(path).await?)
+        .map_err(|e| DocumentDBError::internal_error(format!("Failed to parse JWKS file: {}", e)))
+}
+
+/// Validates a JWT against the signing keys of the identity provider.
+/// The signature, issuer, audience and expiry are checked, and the principal claim names the user.
+pub fn validate_token(
+    options: &OidcOptions,
+    key_set: &JwkSet,
+    token: &str,
+) -> Result<OidcIdentity> {
+    let header = jsonwebtoken::decode_header(token).map_err(invalid_token)?;
+
+    // Only asymmetric keys are accepted, a JWKS is public
+    if matches!(
+        header.alg,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+        return Err(authentication_failed(format!(
+            "Unsupported token algorithm: {:?}",
+            header.alg
+        )));
+    }
+
+    let key = match header.kid.as_deref() {
+        Some(kid) => key_set.find(kid),
+        None if key_set.keys.len() == 1 => key_set.keys.first(),
+        None => None,
+    }
+    .ok_or(authentication_failed(
+        "No signing key matches the token".to_string(),
+    ))?;
+
+    if matches!(key.algorithm, AlgorithmParameters::OctetKey(_))
+        || key
+            .common
+            .key_algorithm
+            .is_some_and(|alg| alg.to_string() != format!("{:?}", header.alg))
+    {
+        return Err(authentication_failed(
+            "Signing key does not match the token algorithm".to_string(),
+        ));
+    }
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[&options.issuer]);
+    validation.set_audience(&[&options.audience]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+
+    let claims = jsonwebtoken::decode::<Map<String, Value>>(
+        token,
+        &DecodingKey::from_jwk(key).map_err(invalid_token)?,
+        &validation,
+    )
+    .map_err(invalid_token)?
+    .claims;
+
+    let username = claims
+        .get(options.principal_claim())
+        .and_then(Value::as_str)
+        .filter(|username| !username.is_empty())
+        .ok_or(authentication_failed(format!(
+            "Token is missing the {} claim",
+            options.principal_claim()
+        )))?;
+    let expires_at = claims
+        .get("exp")
+        .and_then(Value::as_u64)
+        .ok_or(authentication_failed(
+            "Token has an invalid exp claim".to_string(),
+        ))?;
+
+    Ok(OidcIdentity {
+        username: username.to_string(),
+        expires_at: UNIX_EPOCH + Duration::from_secs(expires_at),
+    })
+}
+
+fn invalid_token(e: jsonwebtoken::errors::Error) -> DocumentDBError {
+    authentication_failed(format!("Invalid token: {}", e))
+}
+
+fn authentication_failed(msg: String) -> DocumentDBError {
+    DocumentDBError::documentdb_error(ErrorCode::AuthenticationFailed, msg)
+}
@@ -6,7 +6,13 @@
  *-------------------------------------------------------------------------
  */
 
-use std::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use super::{PgDocument, QueryCatalog};
 use crate::{
@@ -18,7 +24,7 @@ use deadpool_postgres::{Hook, HookError, Runtime};
 use tokio::task::JoinHandle;
 use tokio_postgres::{
     types::{ToSql, Type},
-    NoTls, Row,
+    CancelToken, NoTls, Row,
 };
 
 pub type InnerConnection = deadpool_postgres::Object;
@@ -101,8 +107,52 @@ impl ConnectionPool {
 // Will be occur if the wrong one is used.
 #[derive(Debug)]
 pub struct Connection {
-    inner_conn: InnerConnection,
+    // Only taken when the connection is dropped
+    inner_conn: Option<InnerConnection>,
     pub in_transaction: bool,
+
+    // Set when a query was abandoned, the backend may still be running it so it is not returned to the pool
+    abandoned: Arc<AtomicBool>,
+}
+
+/// Cancels the backend query when dropped before `complete` is called, which happens when the future running
+/// the query is dropped because the client disconnected or the connection task was cancelled.
+/// It should span everything sent for a request, so that an abandoned transaction is not returned to the pool.
+pub struct CancelOnDrop {
+    abandoned: Arc<AtomicBool>,
+    cancel_token: Option<CancelToken>,
+}
+
+impl CancelOnDrop {
+    pub fn complete(mut self) {
+        self.cancel_token = None;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        let Some(cancel_token) = self.cancel_token.take() else {
+            return;
+        };
+
+        self.abandoned.store(true, Ordering::Relaxed);
+        tokio::spawn(async move {
+            if let Err(e) = cancel_token.cancel_query(NoTls).await {
+                log::warn!("Failed to cancel abandoned query: {}", e);
+            }
+        });
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if self.abandoned.load(Ordering::Relaxed) {
+            if let Some(conn) = self.inner_conn.take() {
+                // Closes the connection instead of handing a busy backend to the next request
+                drop(deadpool_postgres::Object::take(conn));
+            }
+        }
+    }
 }
 
 pub enum TimeoutType {
@@ -142,13 +192,12 @@ impl Connection {
         params: &[&(dyn ToSql + Sync)],
     ) -> Result<Vec<Row>> {
         let statement = self
-            .inner_conn
+            .inner()
             .prepare_typed_cached(query, parameter_types)
             .await?;
-        Ok(self.inner_conn.query(&statement, params).await?)
+        Ok(self.inner().query(&statement, params).await?)
     }
 
-    #[allow(unused_mut)]
     pub async fn query(
         &self,
         query: &str,
@@ -156,6 +205,23 @@ impl Connection {
         params: &[&(dyn ToSql + Sync)],
         timeout: Option<Timeout>,
         request_info: &mut RequestInfo<'_>,
+    ) -> Result<Vec<Row>> {
+        // Covers the statement timeout and the BEGIN and COMMIT around the query, not only the query itself
+        let guard = self.cancel_on_drop();
+        let results = self
+            .query_with_timeout(query, parameter_types, params, timeout, request_info)
+            .await;
+        guard.complete();
+        results
+    }
+
+    async fn query_with_timeout(
+        &self,
+        query: &str,
+        parameter_types: &[Type],
+        params: &[&(dyn ToSql + Sync)],
+        timeout: Option<Timeout>,
+        request_info: &mut RequestInfo<'_>,
     ) -> Result<Vec<Row>> {
         match timeout {
             Some(Timeout {
@@ -163,7 +229,7 @@ impl Connection {
                 max_time_ms,
             }) if self.in_transaction => {
                 let set_timeout_start = request_info.request_tracker.start_timer();
-                self.inner_conn
+                self.inner()
                     .batch_execute(&format!("set local statement_timeout to {}", max_time_ms))
                     .await?;
                 request_info.request_tracker.record_duration(
@@ -178,7 +244,7 @@ impl Connection {
                     .record_duration(RequestIntervalKind::ProcessRequest, request_start);
 
                 let set_timeout_start = request_info.request_tracker.start_timer();
-                self.inner_conn
+                self.inner()
                     .batch_execute(&format!(
                         "set local statement_timeout to {}",
                         Duration::from_secs(120).as_millis()
@@ -196,14 +262,14 @@ impl Connection {
                 max_time_ms,
             }) => {
                 let begin_transaction_start = request_info.request_tracker.start_timer();
-                self.inner_conn.batch_execute("BEGIN").await?;
+                self.inner().batch_execute("BEGIN").await?;
                 request_info.request_tracker.record_duration(
                     RequestIntervalKind::PostgresBeginTransaction,
                     begin_transaction_start,
                 );
 
                 let set_timeout_start = request_info.request_tracker.start_timer();
-                self.inner_conn
+                self.inner()
                     .batch_execute(&format!("set local statement_timeout to {}", max_time_ms))
                     .await?;
                 request_info.request_tracker.record_duration(
@@ -215,7 +281,7 @@ impl Connection {
                 let results = match self.query_internal(query, parameter_types, params).await {
                     Ok(results) => Ok(results),
                     Err(e) => {
-                        self.inner_conn.batch_execute("ROLLBACK").await?;
+                        self.inner().batch_execute("ROLLBACK").await?;
                         Err(e)
                     }
                 }?;
@@ -224,7 +290,7 @@ impl Connection {
                     .record_duration(RequestIntervalKind::ProcessRequest, request_start);
 
                 let commit_start = request_info.request_tracker.start_timer();
-                self.inner_conn.batch_execute("COMMIT").await?;
+                self.inner().batch_execute("COMMIT").await?;
                 request_info
                     .request_tracker
                     .record_duration(RequestIntervalKind::PostgresTransactionCommit, commit_start);
@@ -236,7 +302,7 @@ impl Connection {
                 max_time_ms,
             }) => {
                 let set_timeout_start = request_info.request_tracker.start_timer();
-                self.inner_conn
+                self.inner()
                     .batch_execute(&format!("set statement_timeout to {}", max_time_ms))
                     .await?;
                 request_info.request_tracker.record_duration(
@@ -251,7 +317,7 @@ impl Connection {
                     .record_duration(RequestIntervalKind::ProcessRequest, request_start);
 
                 let set_timeout_start = request_info.request_tracker.start_timer();
-                self.inner_conn
+                self.inner()
                     .batch_execute(&format!(
                         "set statement_timeout to {}",
                         Duration::from_secs(120).as_millis()
@@ -295,18 +361,37 @@ impl Connection {
     }
 
     pub async fn batch_execute(&self, query: &str) -> Result<()> {
-        Ok(self.inner_conn.batch_execute(query).await?)
+        Ok(self.inner().batch_execute(query).await?)
     }
 
     pub fn new(conn: InnerConnection, in_transaction: bool) -> Self {
         Connection {
-            inner_conn: conn,
+            inner_conn: Some(conn),
             in_transaction,
+            abandoned: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Returns a guard which cancels the backend query and keeps the connection out of the pool
+    /// when it is dropped before completing.
+    pub fn cancel_on_drop(&self) -> CancelOnDrop {
+        CancelOnDrop {
+            abandoned: self.abandoned.clone(),
+            cancel_token: Some(self.inner().cancel_token()),
         }
     }
 
+    fn inner(&self) -> &InnerConnection {
+        self.inner_conn
+            .as_ref()
+            .expect("Connection is only taken on drop")
+    }
+
     // Should avoid using this in general - Used for explain which has special handling
+    // Queries sent through it should be covered by cancel_on_drop
     pub fn get_inner(&mut self) -> &mut InnerConnection {
-        &mut self.inner_conn
+        self.inner_conn
+            .as_mut()
+            .expect("Connection is only taken on drop")
     }
 }
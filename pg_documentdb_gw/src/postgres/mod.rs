@@ -11,7 +11,7 @@ mod document;
 mod query_catalog;
 mod transaction;
 
-pub use client::{Connection, ConnectionPool, Timeout, TimeoutType};
+pub use client::{CancelOnDrop, Connection, ConnectionPool, Timeout, TimeoutType};
 pub use document::PgDocument;
 pub use query_catalog::create_query_catalog;
 pub use query_catalog::QueryCatalog;
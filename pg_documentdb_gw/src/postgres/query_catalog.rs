@@ -13,9 +13,13 @@ pub struct QueryCatalog {
     // auth.rs
     pub authenticate_with_scram_sha256: String,
     pub salt_and_iterations: String,
+    pub authenticate_with_scram_sha1: String,
+    pub scram_sha1_salt_and_iterations: String,
+    pub user_exists: String,
+    pub authenticate_with_pwd: String,
+    pub scram_sha256_secret: String,
 
     // dataapi.rs (Not needed for OSS)
-    pub authenticate_with_pwd: String,
     pub bson_json_to_bson: String,
     pub bson_to_json_string: String,
 
@@ -78,6 +82,10 @@ pub struct QueryCatalog {
     pub shard_collection: String,
     pub rename_collection: String,
     pub current_op: String,
+    pub kill_op_target: String,
+    pub cancel_backend: String,
+    pub terminate_backend: String,
+    pub is_cluster_admin: String,
     pub coll_mod: String,
     pub get_parameter: String,
 
@@ -103,11 +111,28 @@ impl QueryCatalog {
         &self.salt_and_iterations
     }
 
-    // Dataapi getters
+    pub fn authenticate_with_scram_sha1(&self) -> &str {
+        &self.authenticate_with_scram_sha1
+    }
+
+    pub fn scram_sha1_salt_and_iterations(&self) -> &str {
+        &self.scram_sha1_salt_and_iterations
+    }
+
+    pub fn user_exists(&self) -> &str {
+        &self.user_exists
+    }
+
     pub fn authenticate_with_pwd(&self) -> &str {
         &self.authenticate_with_pwd
     }
 
+    pub fn scram_sha256_secret(&self) -> &str {
+        &self.scram_sha256_secret
+    }
+
+    // Dataapi getters
+
     pub fn bson_json_to_bson(&self) -> &str {
         &self.bson_json_to_bson
     }
@@ -297,6 +322,22 @@ impl QueryCatalog {
         &self.current_op
     }
 
+    pub fn kill_op_target(&self) -> &str {
+        &self.kill_op_target
+    }
+
+    pub fn cancel_backend(&self) -> &str {
+        &self.cancel_backend
+    }
+
+    pub fn terminate_backend(&self) -> &str {
+        &self.terminate_backend
+    }
+
+    pub fn is_cluster_admin(&self) -> &str {
+        &self.is_cluster_admin
+    }
+
     pub fn coll_mod(&self) -> &str {
         &self.coll_mod
     }
@@ -338,6 +379,11 @@ pub fn create_query_catalog() -> QueryCatalog {
             // auth.rs
             authenticate_with_scram_sha256: "SELECT documentdb_api_internal.authenticate_with_scram_sha256($1, $2, $3)".to_string(),
             salt_and_iterations: "SELECT documentdb_api_internal.scram_sha256_get_salt_and_iterations($1)".to_string(),
+            authenticate_with_scram_sha1: "SELECT documentdb_api_internal.authenticate_with_scram_sha1($1, $2, $3)".to_string(),
+            scram_sha1_salt_and_iterations: "SELECT documentdb_api_internal.scram_sha1_get_salt_and_iterations($1)".to_string(),
+            user_exists: "SELECT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = $1 AND rolcanlogin)".to_string(),
+            authenticate_with_pwd: "SELECT documentdb_api_internal.authenticate_with_pwd($1, $2)".to_string(),
+            scram_sha256_secret: "SELECT documentdb_api_internal.scram_sha256_get_secret($1)".to_string(),
 
             // dynamic.rs
             pg_settings: "SELECT name, setting FROM pg_settings WHERE name LIKE 'documentdb.%' OR name IN ('max_connections', 'default_transaction_read_only')".to_string(),
@@ -402,6 +448,10 @@ pub fn create_query_catalog() -> QueryCatalog {
             shard_collection: "SELECT documentdb_api.shard_collection($1, $2, $3, $4)".to_string(),
             rename_collection: "SELECT documentdb_api.rename_collection($1, $2, $3, $4)".to_string(),
             current_op: "SELECT documentdb_api.current_op($1, $2, $3)".to_string(),
+            kill_op_target: "SELECT usename::text, state, pg_has_role($3, 'documentdb_admin_role', 'MEMBER') OR pg_has_role($3, 'pg_signal_backend', 'MEMBER') FROM pg_stat_activity WHERE pid = $1 AND (EXTRACT(epoch FROM query_start) * 1000000)::numeric(20,0)::text = $2".to_string(),
+            cancel_backend: "SELECT pg_cancel_backend($1)".to_string(),
+            terminate_backend: "SELECT pg_terminate_backend($1)".to_string(),
+            is_cluster_admin: "SELECT pg_has_role($1, 'documentdb_admin_role', 'MEMBER')".to_string(),
             coll_mod: "SELECT documentdb_api.coll_mod($1, $2, $3)".to_string(),
             get_parameter: "SELECT documentdb_api.get_parameter($1, $2, $3)".to_string(),
 
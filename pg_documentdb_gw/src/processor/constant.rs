@@ -160,7 +160,7 @@ struct CommandInfo {
     secondary_override_ok: Option<bool>,
 }
 
-static SUPPORTED_COMMANDS : [CommandInfo; 56] = [
+static SUPPORTED_COMMANDS : [CommandInfo; 58] = [
     CommandInfo {
 		command_name: "abortTransaction",
 		admin_only: true,
@@ -449,6 +449,14 @@ static SUPPORTED_COMMANDS : [CommandInfo; 56] = [
 		requires_auth: false,
         secondary_override_ok: None,
 	},
+    CommandInfo {
+		command_name: "killAllSessions",
+		admin_only: false,
+		help: "kill all logical sessions, for the given users",
+		secondary_ok: false,
+		requires_auth: true,
+        secondary_override_ok: None,
+    },
     CommandInfo {
 		command_name: "killCursors",
 		admin_only: false,
@@ -529,6 +537,14 @@ static SUPPORTED_COMMANDS : [CommandInfo; 56] = [
 		requires_auth: true,
         secondary_override_ok: None,
     },
+    CommandInfo {
+		command_name: "refreshSessions",
+		admin_only: false,
+		help: "renew a set of logical sessions",
+		secondary_ok: false,
+		requires_auth: true,
+        secondary_override_ok: None,
+    },
     CommandInfo {
 		command_name: "renameCollection",
 		admin_only: true,
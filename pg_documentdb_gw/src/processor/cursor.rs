@@ -44,7 +44,7 @@ pub async fn save_cursor(
 
 pub async fn process_kill_cursors(
     request: &Request<'_>,
-    context: &ConnectionContext,
+    context: &mut ConnectionContext,
 ) -> Result<Response> {
     let _ = request
         .document()
@@ -70,6 +70,10 @@ pub async fn process_kill_cursors(
         ))?;
         cursor_ids.push(cursor);
     }
+    for cursor in &cursor_ids {
+        context.legacy_cursor_positions.remove(cursor);
+    }
+
     let (removed_cursors, missing_cursors) = context
         .service_context
         .kill_cursors(context.auth_state.username()?, &cursor_ids)
@@ -169,3 +173,13 @@ pub async fn process_get_more(
 
     Ok(Response::Pg(PgResponse::new(results)))
 }
+
+/// Returns the id of the cursor in a cursor response, 0 once the cursor is exhausted.
+pub fn cursor_id(response: &Response) -> Result<i64> {
+    response
+        .as_raw_document()?
+        .get_document("cursor")
+        .map_err(DocumentDBError::pg_response_invalid)?
+        .get_i64("id")
+        .map_err(DocumentDBError::pg_response_invalid)
+}
@@ -11,13 +11,16 @@ use std::{
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use bson::rawdoc;
+use bson::{rawdoc, RawArrayBuf};
 
 use crate::{
+    auth::{self, ScramMechanism},
     configuration::DynamicConfiguration,
-    context::ConnectionContext,
+    context::{ConnectionContext, LOGICAL_SESSION_TIMEOUT_MINUTES},
     error::{DocumentDBError, ErrorCode, Result},
-    protocol::{MAX_BSON_OBJECT_SIZE, MAX_MESSAGE_SIZE_BYTES, OK_SUCCEEDED},
+    protocol::{
+        compression::Compressor, MAX_BSON_OBJECT_SIZE, MAX_MESSAGE_SIZE_BYTES, OK_SUCCEEDED,
+    },
     requests::Request,
     responses::{RawResponse, Response},
 };
@@ -55,16 +58,33 @@ pub async fn process(
         "maxMessageSizeBytes": MAX_MESSAGE_SIZE_BYTES,
         "maxWriteBatchSize": dynamic_configuration.max_write_batch_size().await,
         "localTime": local_time,
-        "logicalSessionTimeoutMinutes": 30,
+        "logicalSessionTimeoutMinutes": LOGICAL_SESSION_TIMEOUT_MINUTES as i32,
         "minWireVersion": 0,
         "maxWireVersion": dynamic_configuration.server_version().await.max_wire_protocol(),
         "readOnly": dynamic_configuration.read_only().await,
         "connectionId": connection_context.connection_id,
-        "saslSupportedMechs": ["SCRAM-SHA-256"],
         "internal": dynamic_configuration.topology(),
         "ok": OK_SUCCEEDED,
     };
 
+    if let Some(mechs) = sasl_supported_mechs(request, connection_context).await? {
+        response_doc.append("saslSupportedMechs", mechs);
+    }
+
+    if !connection_context.auth_state.authorized {
+        if let Ok(speculative) = request.document().get_document("speculativeAuthenticate") {
+            if let Some(response) =
+                auth::speculative_authenticate(connection_context, speculative).await
+            {
+                response_doc.append("speculativeAuthenticate", response);
+            }
+        }
+    }
+
+    if let Some(compression) = negotiate_compression(request, connection_context)? {
+        response_doc.append("compression", compression);
+    }
+
     // Add the operationTime field if change streams GUC is enabled
     if dynamic_configuration.enable_change_streams().await {
         response_doc.append(
@@ -78,3 +98,75 @@ pub async fn process(
 
     Ok(Response::Raw(RawResponse(response_doc)))
 }
+
+// Without a user the default mechanism is advertised, otherwise the mechanisms the user has credentials for.
+// A user without any credentials gets no saslSupportedMechs, as with an unknown user or a throttled client.
+async fn sasl_supported_mechs(
+    request: &Request<'_>,
+    connection_context: &ConnectionContext,
+) -> Result<Option<RawArrayBuf>> {
+    let mut mechs = RawArrayBuf::new();
+    let Some(username) = requested_sasl_user(request)? else {
+        mechs.push(ScramMechanism::Sha256.name());
+        return Ok(Some(mechs));
+    };
+
+    for mechanism in [ScramMechanism::Sha1, ScramMechanism::Sha256] {
+        if auth::has_scram_credentials(connection_context, mechanism, username).await? {
+            mechs.push(mechanism.name());
+        }
+    }
+    Ok((!mechs.is_empty()).then_some(mechs))
+}
+
+// The user is given as "<db>.<username>", where the username may itself contain dots.
+fn requested_sasl_user<'a>(request: &'a Request<'_>) -> Result<Option<&'a str>> {
+    let Some(user) = request.document().get("saslSupportedMechs")? else {
+        return Ok(None);
+    };
+    let user = user.as_str().ok_or(DocumentDBError::type_mismatch(
+        "saslSupportedMechs should be a string".to_string(),
+    ))?;
+    Ok(user.split_once('.').map(|(_, username)| username))
+}
+
+// Intersect the client's requested compressors with the configured ones, keeping the client's order.
+fn negotiate_compression(
+    request: &Request<'_>,
+    connection_context: &mut ConnectionContext,
+) -> Result<Option<RawArrayBuf>> {
+    let requested = match request.document().get("compression")? {
+        Some(requested) => requested.as_array().ok_or(DocumentDBError::type_mismatch(
+            "compression should be an array".to_string(),
+        ))?,
+        None => return Ok(None),
+    };
+
+    let available = connection_context
+        .service_context
+        .setup_configuration()
+        .compressors();
+
+    let mut negotiated = Vec::new();
+    for name in requested {
+        let name = name?.as_str().ok_or(DocumentDBError::type_mismatch(
+            "compression entries should be strings".to_string(),
+        ))?;
+        if let Some(compressor) = Compressor::from_name(name) {
+            if available.contains(&compressor) && !negotiated.contains(&compressor) {
+                negotiated.push(compressor);
+            }
+        }
+    }
+
+    connection_context.compressors = negotiated;
+    if connection_context.compressors.is_empty() {
+        return Ok(None);
+    }
+
+    let mut compression = RawArrayBuf::new();
+    for compressor in &connection_context.compressors {
+        compression.push(compressor.name());
+    }
+    Ok(Some(compression))
+}
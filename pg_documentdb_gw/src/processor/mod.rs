@@ -16,4 +16,5 @@ mod session;
 mod transaction;
 mod users;
 
-pub use process::process_request;
+pub use cursor::{cursor_id, process_get_more};
+pub use process::{parse_op_id, process_request, retryable_write_key};
@@ -12,20 +12,20 @@ use std::{
     time::{Duration, Instant},
 };
 
-use bson::{spec::ElementType, RawBsonRef, RawDocumentBuf};
+use bson::{rawdoc, spec::ElementType, RawBsonRef, RawDocumentBuf};
 use deadpool_postgres::{HookError, PoolError};
 use tokio_postgres::{error::SqlState, types::Type};
 
 use crate::{
     bson::convert_to_bool,
     configuration::DynamicConfiguration,
-    context::ConnectionContext,
+    context::{ConnectionContext, RetryableWrite, RetryableWriteKey},
     error::{DocumentDBError, ErrorCode, Result},
     explain,
     postgres::{PgDocument, Timeout},
-    protocol,
+    protocol::{self, OK_SUCCEEDED},
     requests::{Request, RequestInfo, RequestType},
-    responses::{PgResponse, Response},
+    responses::{PgResponse, RawResponse, Response},
 };
 
 use super::{constant, cursor, delete, indexing, ismaster, session, transaction, users};
@@ -40,6 +40,71 @@ pub async fn process_request(
     request: &Request<'_>,
     request_info: &mut RequestInfo<'_>,
     connection_context: &mut ConnectionContext,
+) -> Result<Response> {
+    // Requests allowed before authentication, such as hello, may carry an lsid but do not belong to a user
+    if let Some(session_id) = request_info
+        .session_id
+        .filter(|_| connection_context.auth_state.authorized)
+    {
+        connection_context
+            .service_context
+            .session_store()
+            .touch(session_id, connection_context.auth_state.username()?)
+            .await?;
+    }
+
+    let Some(key) = retryable_write_key(request, request_info) else {
+        return execute_request(request, request_info, connection_context).await;
+    };
+
+    // A retried statement which already completed gets its original response instead of running twice
+    let guard = match connection_context
+        .service_context
+        .retryable_write_store()
+        .begin(key)
+        .await?
+    {
+        RetryableWrite::Replay(response) => return Ok(Response::Raw(RawResponse(response))),
+        RetryableWrite::Execute(guard) => guard,
+    };
+
+    let result = execute_request(request, request_info, connection_context).await;
+    let response = match &result {
+        Ok(response) => match response.as_raw_document() {
+            Ok(response) => Some(response.to_owned()),
+            Err(e) => {
+                // Still complete the statement, so that retries waiting for it are not stuck
+                guard.complete(None).await;
+                return Err(e);
+            }
+        },
+        Err(_) => None,
+    };
+    guard.complete(response).await;
+    result
+}
+
+/// Identifies the statement when the request is a retryable write outside of a multi-statement transaction.
+pub fn retryable_write_key(
+    request: &Request<'_>,
+    request_info: &RequestInfo<'_>,
+) -> Option<RetryableWriteKey> {
+    let transaction_info = request_info.transaction_info.as_ref()?;
+    if !transaction_info.auto_commit || !request.is_retryable_write() {
+        return None;
+    }
+
+    Some(RetryableWriteKey {
+        session_id: request_info.session_id?.to_vec(),
+        transaction_number: transaction_info.transaction_number,
+        statement_id: request.document().get_i32("stmtId").unwrap_or(0),
+    })
+}
+
+async fn execute_request(
+    request: &Request<'_>,
+    request_info: &mut RequestInfo<'_>,
+    connection_context: &mut ConnectionContext,
 ) -> Result<Response> {
     let dynamic_config = connection_context.dynamic_configuration();
     transaction::handle(request, request_info, connection_context).await?;
@@ -132,11 +197,12 @@ pub async fn process_request(
                 indexing::process_list_indexes(request, request_info, connection_context).await
             }
             RequestType::Ping => Ok(constant::ok_response()),
-            RequestType::SaslContinue | RequestType::SaslStart | RequestType::Logout => {
-                Err(DocumentDBError::internal_error(
-                    "Command should have been handled by Auth".to_string(),
-                ))
-            }
+            RequestType::Authenticate
+            | RequestType::SaslContinue
+            | RequestType::SaslStart
+            | RequestType::Logout => Err(DocumentDBError::internal_error(
+                "Command should have been handled by Auth".to_string(),
+            )),
             RequestType::Update => process_update(request, request_info, connection_context).await,
             RequestType::Validate => {
                 process_validate(request, request_info, connection_context).await
@@ -153,6 +219,7 @@ pub async fn process_request(
             RequestType::CurrentOp => {
                 process_current_op(request, request_info, connection_context).await
             }
+            RequestType::KillOp => process_kill_op(request, request_info, connection_context).await,
             RequestType::CollMod => {
                 process_coll_mod(request, request_info, connection_context).await
             }
@@ -173,6 +240,14 @@ pub async fn process_request(
             RequestType::AbortTransaction => transaction::process_abort(connection_context).await,
             RequestType::ListCommands => constant::list_commands(),
             RequestType::EndSessions => session::end_sessions(request, connection_context).await,
+            RequestType::StartSession => session::start_session(connection_context).await,
+            RequestType::RefreshSessions => {
+                session::refresh_sessions(request, connection_context).await
+            }
+            RequestType::KillSessions => session::kill_sessions(request, connection_context).await,
+            RequestType::KillAllSessions => {
+                session::kill_all_sessions(request, connection_context).await
+            }
             RequestType::ReshardCollection => {
                 process_shard_collection(request, request_info, connection_context, true).await
             }
@@ -749,6 +824,109 @@ async fn process_current_op(
     Ok(Response::Pg(PgResponse::new(results)))
 }
 
+// Global pids reported by currentOp are the node id times this, plus the backend pid
+const GLOBAL_PID_NODE_MULTIPLIER: i64 = 10_000_000_000;
+
+// The node id currentOp gives the backends of the node the gateway is connected to
+const LOCAL_NODE_ID: i64 = 1;
+
+/// Kills an operation reported by currentOp, whose opid is "<global pid>:<query start in microseconds>".
+/// A running query is cancelled, and a backend idle in a transaction is terminated to release its locks.
+/// Only the user running the operation or a cluster administrator may kill it.
+async fn process_kill_op(
+    request: &Request<'_>,
+    request_info: &mut RequestInfo<'_>,
+    context: &ConnectionContext,
+) -> Result<Response> {
+    if request_info.db()? != "admin" {
+        return Err(DocumentDBError::unauthorized(
+            "killOp may only be run against the admin database.".to_string(),
+        ));
+    }
+
+    let op = request
+        .document()
+        .get("op")?
+        .ok_or(DocumentDBError::bad_value(
+            "Did not provide \"op\" field".to_string(),
+        ))?;
+    let (pid, query_start) = parse_op_id(op.as_str().ok_or(DocumentDBError::bad_value(
+        format!("Invalid operation id: {:?}", op),
+    ))?)?;
+
+    let user = context.auth_state.username()?;
+    let connection = context.service_context.system_requests_connection().await?;
+    let target = connection
+        .query(
+            context.service_context.query_catalog().kill_op_target(),
+            &[Type::INT4, Type::TEXT, Type::TEXT],
+            &[&pid, &query_start, &user],
+            Timeout::transaction(request_info.max_time_ms),
+            request_info,
+        )
+        .await?;
+
+    // Like MongoDB, an operation which already finished is not an error
+    if let Some(row) = target.first() {
+        let owner: Option<String> = row.try_get(0)?;
+        let state: Option<String> = row.try_get(1)?;
+        let privileged: bool = row.try_get(2)?;
+        if !privileged && owner.as_deref() != Some(user) {
+            return Err(DocumentDBError::unauthorized(
+                "Not authorized to kill an operation of another user.".to_string(),
+            ));
+        }
+
+        let query_catalog = context.service_context.query_catalog();
+        let signal = match state.as_deref() {
+            Some("active") => Some(query_catalog.cancel_backend()),
+            Some(state) if state.starts_with("idle in transaction") => {
+                Some(query_catalog.terminate_backend())
+            }
+            _ => None,
+        };
+        if let Some(signal) = signal {
+            log::info!("User {} killing operation on backend {}", user, pid);
+            connection
+                .query(
+                    signal,
+                    &[Type::INT4],
+                    &[&pid],
+                    Timeout::transaction(request_info.max_time_ms),
+                    request_info,
+                )
+                .await?;
+        }
+    }
+
+    Ok(Response::Raw(RawResponse(rawdoc! {
+        "info": "attempting to kill op",
+        "ok": OK_SUCCEEDED,
+    })))
+}
+
+/// Returns the backend pid and the query start of an opid.
+/// Operations of other nodes are rejected, as the pid only identifies a backend on its own node.
+pub fn parse_op_id(op: &str) -> Result<(i32, &str)> {
+    let invalid = || DocumentDBError::bad_value(format!("Invalid operation id: {}", op));
+
+    let (global_pid, query_start) = op.split_once(':').ok_or_else(invalid)?;
+    let global_pid: i64 = global_pid.parse().map_err(|_| invalid())?;
+    if global_pid < 0 || query_start.is_empty() || !query_start.bytes().all(|b| b.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    if global_pid / GLOBAL_PID_NODE_MULTIPLIER != LOCAL_NODE_ID {
+        return Err(DocumentDBError::bad_value(format!(
+            "Operation {} does not belong to the node this gateway is connected to",
+            op
+        )));
+    }
+    let pid = i32::try_from(global_pid % GLOBAL_PID_NODE_MULTIPLIER).map_err(|_| invalid())?;
+    Ok((pid, query_start))
+}
+
 async fn process_coll_mod(
     request: &Request<'_>,
     request_info: &mut RequestInfo<'_>,
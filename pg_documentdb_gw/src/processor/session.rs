@@ -6,41 +6,180 @@
  *-------------------------------------------------------------------------
  */
 
+use bson::{rawdoc, spec::BinarySubtype};
+use tokio_postgres::types::Type;
+use uuid::Uuid;
+
 use crate::{
-    context::ConnectionContext,
+    context::{ConnectionContext, LOGICAL_SESSION_TIMEOUT_MINUTES},
     error::{DocumentDBError, Result},
-    requests::Request,
-    responses::Response,
+    protocol::OK_SUCCEEDED,
+    requests::{Request, RequestInfo},
+    responses::{RawResponse, Response},
 };
 
+pub async fn start_session(context: &mut ConnectionContext) -> Result<Response> {
+    let session_id = Uuid::new_v4();
+    context
+        .service_context
+        .session_store()
+        .touch(session_id.as_bytes(), context.auth_state.username()?)
+        .await?;
+
+    Ok(Response::Raw(RawResponse(rawdoc! {
+        "id": {
+            "id": bson::Binary {
+                subtype: BinarySubtype::Uuid,
+                bytes: session_id.as_bytes().to_vec(),
+            },
+        },
+        "timeoutMinutes": LOGICAL_SESSION_TIMEOUT_MINUTES as i32,
+        "ok": OK_SUCCEEDED,
+    })))
+}
+
+pub async fn refresh_sessions(
+    request: &Request<'_>,
+    context: &mut ConnectionContext,
+) -> Result<Response> {
+    let user = context.auth_state.username()?;
+    for session_id in session_ids(request, "refreshSessions")? {
+        context
+            .service_context
+            .session_store()
+            .touch(session_id, user)
+            .await?;
+    }
+    Ok(Response::ok())
+}
+
 pub async fn end_sessions(
     request: &Request<'_>,
     context: &mut ConnectionContext,
 ) -> Result<Response> {
-    let store = context.service_context.transaction_store();
+    let session_ids = session_ids(request, "endSessions")?;
+    check_session_owners(context, &session_ids).await?;
+    for session_id in session_ids {
+        context.service_context.end_session(session_id).await
+    }
+    Ok(Response::ok())
+}
+
+// Kills the given sessions, or all sessions of the user when none are given
+pub async fn kill_sessions(
+    request: &Request<'_>,
+    context: &mut ConnectionContext,
+) -> Result<Response> {
+    let session_ids = session_ids(request, "killSessions")?;
+    check_session_owners(context, &session_ids).await?;
+
+    let mut session_ids: Vec<Vec<u8>> = session_ids.into_iter().map(|id| id.to_vec()).collect();
+    if session_ids.is_empty() {
+        session_ids = context
+            .service_context
+            .session_store()
+            .session_ids(&[context.auth_state.username()?])
+            .await;
+    }
+
+    for session_id in session_ids {
+        context.service_context.end_session(&session_id).await
+    }
+    Ok(Response::ok())
+}
+
+// Kills the sessions of the users matched by { user, db } patterns, or all sessions when none are given.
+// Killing the sessions of other users requires a cluster administrator.
+pub async fn kill_all_sessions(
+    request: &Request<'_>,
+    context: &mut ConnectionContext,
+) -> Result<Response> {
+    let mut users = Vec::new();
     for value in request
         .document()
-        .get_array("endSessions")
+        .get_array("killAllSessions")
         .map_err(DocumentDBError::parse_failure())?
     {
-        let value = value?;
-        let session_id = value
-            .as_document()
-            .ok_or(DocumentDBError::bad_value(
-                "Session should be a document".to_string(),
-            ))?
-            .get_binary("id")
-            .map_err(DocumentDBError::parse_failure())?
-            .bytes;
-
-        // Best effort abort any transaction for the session
-        let _ = store.abort(session_id).await;
-
-        // Remove all cursors for the session
+        users.push(
+            value?
+                .as_document()
+                .ok_or(DocumentDBError::bad_value(
+                    "User pattern should be a document".to_string(),
+                ))?
+                .get_str("user")
+                .map_err(DocumentDBError::parse_failure())?,
+        );
+    }
+
+    let user = context.auth_state.username()?;
+    if (users.is_empty() || users.iter().any(|u| *u != user))
+        && !is_cluster_admin(context, user).await?
+    {
+        return Err(DocumentDBError::unauthorized(
+            "Not authorized to kill the sessions of other users".to_string(),
+        ));
+    }
+
+    for session_id in context
+        .service_context
+        .session_store()
+        .session_ids(&users)
+        .await
+    {
+        context.service_context.end_session(&session_id).await
+    }
+    Ok(Response::ok())
+}
+
+async fn check_session_owners(context: &ConnectionContext, session_ids: &[&[u8]]) -> Result<()> {
+    let user = context.auth_state.username()?;
+    for session_id in session_ids {
         context
             .service_context
-            .invalidate_cursors_by_session(session_id)
-            .await
+            .session_store()
+            .check_owner(session_id, user)
+            .await?;
     }
-    Ok(Response::ok())
+    Ok(())
+}
+
+async fn is_cluster_admin(context: &ConnectionContext, user: &str) -> Result<bool> {
+    Ok(context
+        .service_context
+        .system_requests_connection()
+        .await?
+        .query(
+            context.service_context.query_catalog().is_cluster_admin(),
+            &[Type::TEXT],
+            &[&user],
+            None,
+            &mut RequestInfo::new(),
+        )
+        .await?
+        .first()
+        .ok_or(DocumentDBError::pg_response_empty())?
+        .try_get(0)?)
+}
+
+// Reads the ids of the session documents in the command's array
+fn session_ids<'a>(request: &'a Request<'_>, command: &str) -> Result<Vec<&'a [u8]>> {
+    let mut session_ids = Vec::new();
+    for value in request
+        .document()
+        .get_array(command)
+        .map_err(DocumentDBError::parse_failure())?
+    {
+        let value = value?;
+        session_ids.push(
+            value
+                .as_document()
+                .ok_or(DocumentDBError::bad_value(
+                    "Session should be a document".to_string(),
+                ))?
+                .get_binary("id")
+                .map_err(DocumentDBError::parse_failure())?
+                .bytes,
+        );
+    }
+    Ok(session_ids)
 }
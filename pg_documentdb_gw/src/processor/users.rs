@@ -53,6 +53,11 @@ pub(crate) async fn process_drop_user(
             &mut request_info,
         )
         .await?;
+
+    // Only this process's cache is invalidated, other gateways keep the old keys until their TTL expires
+    if let Ok(user) = request.document().get_str("dropUser") {
+        context.service_context.scram_cache().invalidate(user).await;
+    }
     Ok(Response::Pg(PgResponse::new(results)))
 }
 
@@ -73,6 +78,11 @@ pub(crate) async fn process_update_user(
             &mut request_info,
         )
         .await?;
+
+    // Only this process's cache is invalidated, other gateways keep the old keys until their TTL expires
+    if let Ok(user) = request.document().get_str("updateUser") {
+        context.service_context.scram_cache().invalidate(user).await;
+    }
     Ok(Response::Pg(PgResponse::new(results)))
 }
 
@@ -0,0 +1,133 @@
+/*-------------------------------------------------------------------------
+ * Copyright (c) Microsoft Corporation.  All rights reserved.
+ *
+ * src/protocol/compression.rs
+ *
+ *-------------------------------------------------------------------------
+ */
+
+use std::io::{Read, Write};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::error::{DocumentDBError, Result};
+
+/// Length of the OP_COMPRESSED preamble which follows the standard header:
+/// originalOpcode (i32), uncompressedSize (i32) and compressorId (u8).
+pub const COMPRESSED_PREAMBLE_LENGTH: usize =
+    2 * std::mem::size_of::<i32>() + std::mem::size_of::<u8>();
+
+const ZLIB_COMPRESSION_LEVEL: u32 = 6;
+const ZSTD_COMPRESSION_LEVEL: i32 = 6;
+
+/// Compressors as defined by the OP_COMPRESSED spec, identified on the wire by their id.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Compressor {
+    Noop = 0,
+    Snappy = 1,
+    Zlib = 2,
+    Zstd = 3,
+}
+
+impl Compressor {
+    /// The compressors the gateway supports, in order of preference.
+    pub const SUPPORTED: [Compressor; 3] = [Compressor::Snappy, Compressor::Zlib, Compressor::Zstd];
+
+    pub fn from_id(id: u8) -> Option<Compressor> {
+        match id {
+            0 => Some(Compressor::Noop),
+            1 => Some(Compressor::Snappy),
+            2 => Some(Compressor::Zlib),
+            3 => Some(Compressor::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Parses a compressor name as sent in the hello `compression` array.
+    pub fn from_name(name: &str) -> Option<Compressor> {
+        match name {
+            "noop" => Some(Compressor::Noop),
+            "snappy" => Some(Compressor::Snappy),
+            "zlib" => Some(Compressor::Zlib),
+            "zstd" => Some(Compressor::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compressor::Noop => "noop",
+            Compressor::Snappy => "snappy",
+            Compressor::Zlib => "zlib",
+            Compressor::Zstd => "zstd",
+        }
+    }
+
+    pub fn compress(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Compressor::Noop => Ok(bytes.to_vec()),
+            Compressor::Snappy => snap::raw::Encoder::new()
+                .compress_vec(bytes)
+                .map_err(|e| compression_error(self, e)),
+            Compressor::Zlib => {
+                let mut encoder =
+                    ZlibEncoder::new(Vec::new(), Compression::new(ZLIB_COMPRESSION_LEVEL));
+                encoder.write_all(bytes)?;
+                Ok(encoder.finish()?)
+            }
+            Compressor::Zstd => zstd::bulk::compress(bytes, ZSTD_COMPRESSION_LEVEL)
+                .map_err(|e| compression_error(self, e)),
+        }
+    }
+
+    /// Decompresses `bytes`, failing if the result is not exactly `uncompressed_size` bytes long.
+    pub fn decompress(&self, bytes: &[u8], uncompressed_size: usize) -> Result<Vec<u8>> {
+        let decompressed = match self {
+            Compressor::Noop => bytes.to_vec(),
+            Compressor::Snappy => {
+                if snap::raw::decompress_len(bytes).map_err(|e| compression_error(self, e))?
+                    != uncompressed_size
+                {
+                    return Err(uncompressed_size_mismatch());
+                }
+                snap::raw::Decoder::new()
+                    .decompress_vec(bytes)
+                    .map_err(|e| compression_error(self, e))?
+            }
+            Compressor::Zlib => {
+                let mut decompressed = Vec::with_capacity(uncompressed_size);
+                // Read one byte past the promised size so that oversized payloads are detected
+                ZlibDecoder::new(bytes)
+                    .take(uncompressed_size as u64 + 1)
+                    .read_to_end(&mut decompressed)
+                    .map_err(|e| compression_error(self, e))?;
+                decompressed
+            }
+            Compressor::Zstd => zstd::bulk::decompress(bytes, uncompressed_size)
+                .map_err(|e| compression_error(self, e))?,
+        };
+
+        if decompressed.len() != uncompressed_size {
+            return Err(uncompressed_size_mismatch());
+        }
+        Ok(decompressed)
+    }
+}
+
+fn compression_error<E: std::fmt::Display>(compressor: &Compressor, e: E) -> DocumentDBError {
+    DocumentDBError::bad_value(format!(
+        "Failed to process {} compressed message: {}",
+        compressor.name(),
+        e
+    ))
+}
+
+fn uncompressed_size_mismatch() -> DocumentDBError {
+    DocumentDBError::bad_value(
+        "Decompressed message was not the length promised by uncompressedSize".to_string(),
+    )
+}
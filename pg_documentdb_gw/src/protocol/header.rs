@@ -9,7 +9,10 @@
 use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use uuid::Uuid;
 
-use crate::{error::DocumentDBError, protocol::opcode::OpCode};
+use crate::{
+    error::DocumentDBError,
+    protocol::{compression::Compressor, opcode::OpCode, MAX_MESSAGE_SIZE_BYTES},
+};
 
 // This represents the message header (first 16 bytes of a message).
 // Uniqueness is not guaranteed since request_id and/or response_to can wrap, so we
@@ -21,6 +24,15 @@ pub struct Header {
     pub response_to: i32,
     pub op_code: OpCode,
     pub activity_id: String, // won't be written to the stream
+
+    // Set when the request arrived as OP_COMPRESSED, the response is compressed with the same compressor
+    pub compressor: Option<Compressor>,
+
+    // Set when OP_MSG responses to this request should carry a CRC-32C checksum
+    pub response_checksum: bool,
+
+    // Set when cursor batches in OP_MSG responses should be written as a kind-1 document sequence
+    pub response_document_sequences: bool,
 }
 
 impl Header {
@@ -30,20 +42,32 @@ impl Header {
         &self,
         stream: &mut W,
     ) -> Result<(), DocumentDBError> {
-        stream.write_all(&self.length.to_le_bytes()).await?;
-        stream.write_all(&self.request_id.to_le_bytes()).await?;
-        stream.write_all(&self.response_to.to_le_bytes()).await?;
-        stream
-            .write_all(&(self.op_code as i32).to_le_bytes())
-            .await?;
-
+        stream.write_all(&self.to_bytes()).await?;
         Ok(())
     }
 
+    /// Serializes the wire fields of the header.
+    pub fn to_bytes(&self) -> [u8; Self::LENGTH] {
+        let mut bytes = [0_u8; Self::LENGTH];
+        bytes[0..4].copy_from_slice(&self.length.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.request_id.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.response_to.to_le_bytes());
+        bytes[12..16].copy_from_slice(&(self.op_code as i32).to_le_bytes());
+        bytes
+    }
+
     pub(crate) async fn read_from<R: tokio::io::AsyncRead + Unpin + Send>(
         reader: &mut R,
     ) -> Result<Self, DocumentDBError> {
         let length = reader.read_i32_le().await?;
+        if length < Self::LENGTH as i32 || length > MAX_MESSAGE_SIZE_BYTES {
+            return Err(DocumentDBError::protocol_error(format!(
+                "Message length {} is outside of the allowed range [{}, {}]",
+                length,
+                Self::LENGTH,
+                MAX_MESSAGE_SIZE_BYTES
+            )));
+        }
         let request_id = reader.read_i32_le().await?;
         let response_to = reader.read_i32_le().await?;
         let op_code = OpCode::from_value(reader.read_i32_le().await?);
@@ -53,6 +77,9 @@ impl Header {
             response_to,
             op_code,
             activity_id: Uuid::new_v4().to_string(),
+            compressor: None,
+            response_checksum: false,
+            response_document_sequences: false,
         })
     }
 }
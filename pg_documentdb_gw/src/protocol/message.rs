@@ -11,9 +11,14 @@ use std::io::Cursor;
 use ::bson::RawDocument;
 use bitflags::bitflags;
 
-use crate::{bson, error::DocumentDBError};
+use crate::{
+    bson,
+    error::{DocumentDBError, ErrorCode},
+};
 
-use super::{reader::str_from_u8_nul_utf8, util::SyncLittleEndianRead};
+use super::{
+    header::Header, opcode::OpCode, reader::str_from_u8_nul_utf8, util::SyncLittleEndianRead,
+};
 
 #[derive(Debug)]
 pub struct Message<'a> {
@@ -24,36 +29,53 @@ pub struct Message<'a> {
     pub(crate) _request_id: Option<i32>,
 }
 
-const _DEFAULT_MAX_MESSAGE_SIZE_BYTES: i32 = 48 * 1024 * 1024;
-
 impl Message<'_> {
     pub fn read_from_op_msg(
         mut reader: Cursor<&[u8]>,
+        request_id: i32,
         response_to: i32,
     ) -> Result<Message, DocumentDBError> {
-        let mut length_remaining = reader.get_ref().len();
-        let flags = MessageFlags::from_bits_truncate(reader.read_u32_sync()?);
-        length_remaining -= std::mem::size_of::<u32>();
+        let length = reader.get_ref().len();
+        let flags = MessageFlags::from_bits_truncate(reader.read_u32_sync().map_err(|_| {
+            DocumentDBError::protocol_error("Message flags are truncated".to_string())
+        })?);
+
+        // The checksum, when present, occupies the last 4 bytes after the sections
+        let checksum_length = if flags.contains(MessageFlags::CHECKSUM_PRESENT) {
+            std::mem::size_of::<u32>()
+        } else {
+            0
+        };
+        let sections_end = length
+            .checked_sub(checksum_length)
+            .filter(|end| *end >= reader.position() as usize)
+            .ok_or(DocumentDBError::protocol_error(
+                "Message request was not the length promised".to_string(),
+            ))?;
 
         let mut sections = Vec::new();
-        let mut bytes_read = 0;
-
-        while length_remaining - bytes_read > 4 {
+        while (reader.position() as usize) < sections_end {
             let section = MessageSection::read(&mut reader)?;
-            bytes_read += section.length() + 1; // 1 for the payload type
+            if reader.position() as usize > sections_end {
+                return Err(DocumentDBError::protocol_error(
+                    "Message section extends past the end of the message".to_string(),
+                ));
+            }
             sections.push(section);
         }
 
-        length_remaining -= bytes_read;
-
         let mut checksum = None;
-
-        if length_remaining == 4 && flags.contains(MessageFlags::CHECKSUM_PRESENT) {
-            checksum = Some(reader.read_u32_sync()?);
-        } else if length_remaining != 0 {
-            return Err(DocumentDBError::bad_value(
-                "Message request was not the length promised".to_string(),
-            ));
+        if checksum_length > 0 {
+            let expected = reader.read_u32_sync()?;
+            let actual =
+                op_msg_checksum(request_id, response_to, &reader.get_ref()[..sections_end]);
+            if expected != actual {
+                return Err(DocumentDBError::documentdb_error(
+                    ErrorCode::ChecksumMismatch,
+                    "OP_MSG checksum does not match contents".to_string(),
+                ));
+            }
+            checksum = Some(expected);
         }
 
         // Some drivers (mongo.exe) don't put the command document first.
@@ -64,62 +86,91 @@ impl Message<'_> {
             _flags: flags,
             sections,
             _checksum: checksum,
-            _request_id: None,
+            _request_id: Some(request_id),
         })
     }
 }
 
+/// Computes the CRC-32C of an OP_MSG from its header fields and the body preceding the checksum.
+/// The checksum covers the whole message, header included, except for the checksum itself.
+pub(crate) fn op_msg_checksum(request_id: i32, response_to: i32, body: &[u8]) -> u32 {
+    let length = (Header::LENGTH + body.len() + std::mem::size_of::<u32>()) as i32;
+
+    let mut header = [0_u8; Header::LENGTH];
+    header[0..4].copy_from_slice(&length.to_le_bytes());
+    header[4..8].copy_from_slice(&request_id.to_le_bytes());
+    header[8..12].copy_from_slice(&response_to.to_le_bytes());
+    header[12..16].copy_from_slice(&(OpCode::Msg as i32).to_le_bytes());
+
+    crc32c::crc32c_append(crc32c::crc32c(&header), body)
+}
+
 /// Represents a section as defined by the OP_MSG spec.
 #[derive(Debug)]
 pub(crate) enum MessageSection<'a> {
     Document(&'a RawDocument),
     Sequence {
-        size: i32,
+        _size: i32,
         _identifier: &'a str,
         documents: &'a [u8],
     },
 }
 
 impl MessageSection<'_> {
-    fn length(&self) -> usize {
-        match self {
-            Self::Document(r) => r.as_bytes().len(),
-            Self::Sequence { size, .. } => *size as usize,
-        }
-    }
-
     /// Reads bytes from `reader` and deserializes them into a MessageSection.
+    /// Every length is checked against the remaining bytes before it is used to slice.
     fn read<'b>(reader: &mut Cursor<&'b [u8]>) -> Result<MessageSection<'b>, DocumentDBError> {
         let payload_type = reader.read_u8_sync()?;
 
-        if payload_type == 0 {
-            let (doc, _) = bson::read_document_bytes(reader)?;
-
-            return Ok(MessageSection::Document(doc));
+        match payload_type {
+            0 => {
+                let (doc, _) = bson::read_document_bytes(reader)?;
+                Ok(MessageSection::Document(doc))
+            }
+            1 => {
+                let buffer: &'b [u8] = reader.get_ref();
+                let start = reader.position() as usize;
+                let size = reader.read_i32_sync().map_err(|_| {
+                    DocumentDBError::protocol_error(
+                        "Document sequence size is truncated".to_string(),
+                    )
+                })?;
+
+                // The size covers itself, the identifier and the documents
+                let end = usize::try_from(size)
+                    .ok()
+                    .filter(|size| *size > std::mem::size_of::<i32>())
+                    .and_then(|size| start.checked_add(size))
+                    .filter(|end| *end <= buffer.len())
+                    .ok_or(DocumentDBError::protocol_error(format!(
+                        "Document sequence size {} is invalid with {} bytes remaining",
+                        size,
+                        buffer.len().saturating_sub(start)
+                    )))?;
+
+                let id_start = start + std::mem::size_of::<i32>();
+                let (identifier, id_size) = str_from_u8_nul_utf8(&buffer[id_start..end])?;
+                let documents = &buffer[id_start + id_size + 1..end];
+                reader.set_position(end as u64);
+
+                Ok(MessageSection::Sequence {
+                    _size: size,
+                    _identifier: identifier,
+                    documents,
+                })
+            }
+            _ => Err(DocumentDBError::protocol_error(format!(
+                "Unknown OP_MSG section payload type {}",
+                payload_type
+            ))),
         }
-
-        let size = reader.read_i32_sync()? as usize;
-        let end = reader.position() + (size - 4) as u64;
-
-        let (identifier, id_size) =
-            str_from_u8_nul_utf8(&reader.get_ref()[reader.position() as usize..])?;
-        reader.set_position(reader.position() + id_size as u64 + 1);
-
-        let pos = reader.position() as usize;
-        let documents = &reader.get_ref()[pos..end as usize];
-        reader.set_position(end);
-        Ok(MessageSection::Sequence {
-            size: size as i32,
-            _identifier: identifier,
-            documents,
-        })
     }
 
     fn payload_type(&self) -> i32 {
         match self {
             MessageSection::Document(_) => 0,
             MessageSection::Sequence {
-                size: _,
+                _size: _,
                 _identifier: _,
                 documents: _,
             } => 1,
@@ -135,3 +186,39 @@ bitflags! {
         const EXHAUST_ALLOWED  = 0b_0000_0000_0000_0001_0000_0000_0000_0000;
     }
 }
+
+bitflags! {
+    /// Represents the bitwise flags for an OP_QUERY as defined in the legacy spec.
+    pub(crate) struct QueryFlags: u32 {
+        const TAILABLE_CURSOR   = 0b_0000_0010;
+        const SECONDARY_OK      = 0b_0000_0100;
+        const NO_CURSOR_TIMEOUT = 0b_0001_0000;
+        const AWAIT_DATA        = 0b_0010_0000;
+        const EXHAUST           = 0b_0100_0000;
+        const PARTIAL           = 0b_1000_0000;
+    }
+}
+
+bitflags! {
+    /// Represents the bitwise flags for an OP_UPDATE as defined in the legacy spec.
+    pub(crate) struct UpdateFlags: u32 {
+        const UPSERT       = 0b_0000_0001;
+        const MULTI_UPDATE = 0b_0000_0010;
+    }
+}
+
+bitflags! {
+    /// Represents the bitwise flags for an OP_DELETE as defined in the legacy spec.
+    pub(crate) struct DeleteFlags: u32 {
+        const SINGLE_REMOVE = 0b_0000_0001;
+    }
+}
+
+bitflags! {
+    /// Represents the bitwise flags for an OP_REPLY as defined in the legacy spec.
+    pub(crate) struct ReplyFlags: u32 {
+        const CURSOR_NOT_FOUND = 0b_0000_0001;
+        const QUERY_FAILURE    = 0b_0000_0010;
+        const AWAIT_CAPABLE    = 0b_0000_1000;
+    }
+}
@@ -8,13 +8,18 @@
 
 use crate::error::{DocumentDBError, Result};
 
+pub mod compression;
 pub mod header;
 pub mod message;
 pub mod opcode;
+pub mod proxy;
 pub mod reader;
 pub mod util;
 
 pub const MAX_BSON_OBJECT_SIZE: i32 = 16 * 1024 * 1024;
+// Command documents may exceed the user document limit by this much to hold the command fields
+pub const MAX_BSON_INTERNAL_SIZE: i32 = MAX_BSON_OBJECT_SIZE + 16 * 1024;
+pub const MIN_BSON_OBJECT_SIZE: i32 = 5;
 pub const MAX_MESSAGE_SIZE_BYTES: i32 = 48000000;
 pub const LOGICAL_SESSION_TIMEOUT_MINUTES: u8 = 30;
 
@@ -0,0 +1,155 @@
+/*-------------------------------------------------------------------------
+ * Copyright (c) Microsoft Corporation.  All rights reserved.
+ *
+ * src/protocol/proxy.rs
+ *
+ *-------------------------------------------------------------------------
+ */
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use ipnet::IpNet;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::error::{DocumentDBError, Result};
+
+const V2_SIGNATURE: [u8; 12] = [
+    0x0D, 0x0A, 0x0D, 0x0A, 0x00, 0x0D, 0x0A, 0x51, 0x55, 0x49, 0x54, 0x0A,
+];
+const V1_PREFIX: &[u8] = b"PROXY ";
+
+// A v1 header, including the CRLF, is at most 107 bytes
+const V1_MAX_LENGTH: usize = 107;
+
+const V2_COMMAND_LOCAL: u8 = 0x0;
+const V2_COMMAND_PROXY: u8 = 0x1;
+const V2_FAMILY_INET: u8 = 0x1;
+const V2_FAMILY_INET6: u8 = 0x2;
+
+/// Resolves the client address of a connection accepted from `peer`.
+/// Peers within the trusted proxy networks must send a PROXY header within `timeout`, and the address it carries
+/// replaces theirs. Connections from any other peer are used as is, without reading from the stream.
+pub async fn resolve_client_address<R>(
+    stream: &mut R,
+    peer: SocketAddr,
+    trusted_proxies: &[IpNet],
+    timeout: Duration,
+) -> Result<SocketAddr>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    if !trusted_proxies
+        .iter()
+        .any(|net| net.contains(&peer.ip().to_canonical()))
+    {
+        return Ok(peer);
+    }
+
+    match tokio::time::timeout(timeout, read_proxy_header(stream)).await {
+        Ok(source) => Ok(source?.unwrap_or(peer)),
+        Err(_) => Err(proxy_error("Timed out reading the PROXY header")),
+    }
+}
+
+/// Reads a PROXY protocol v1 or v2 header from the start of the stream.
+/// Returns the original client address, or None when the proxy reports the connection as local or of an unknown family.
+/// Bytes are read one field at a time so nothing past the header is consumed from the stream.
+pub async fn read_proxy_header<R>(stream: &mut R) -> Result<Option<SocketAddr>>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    // Both versions are at least as long as the v2 signature
+    let mut prefix = [0_u8; 12];
+    stream.read_exact(&mut prefix).await?;
+
+    if prefix == V2_SIGNATURE {
+        read_v2(stream).await
+    } else if prefix.starts_with(V1_PREFIX) {
+        read_v1(&prefix, stream).await
+    } else {
+        Err(proxy_error("Connection did not start with a PROXY header"))
+    }
+}
+
+async fn read_v1<R>(prefix: &[u8], stream: &mut R) -> Result<Option<SocketAddr>>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    let mut line = prefix.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LENGTH {
+            return Err(proxy_error("PROXY v1 header is too long"));
+        }
+        line.push(stream.read_u8().await?);
+    }
+
+    let line = std::str::from_utf8(&line[V1_PREFIX.len()..line.len() - 2])
+        .map_err(|_| proxy_error("PROXY v1 header was not valid ascii"))?;
+    let fields: Vec<&str> = line.split(' ').collect();
+    match fields.as_slice() {
+        ["UNKNOWN", ..] => Ok(None),
+        [protocol @ ("TCP4" | "TCP6"), source, _destination, source_port, _destination_port] => {
+            let ip: IpAddr = source
+                .parse()
+                .map_err(|_| proxy_error("PROXY v1 header had an invalid source address"))?;
+            if ip.is_ipv4() != (*protocol == "TCP4") {
+                return Err(proxy_error(
+                    "PROXY v1 source address does not match the protocol",
+                ));
+            }
+            let port: u16 = source_port
+                .parse()
+                .map_err(|_| proxy_error("PROXY v1 header had an invalid source port"))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(proxy_error("PROXY v1 header was malformed")),
+    }
+}
+
+async fn read_v2<R>(stream: &mut R) -> Result<Option<SocketAddr>>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    let version_command = stream.read_u8().await?;
+    let family_protocol = stream.read_u8().await?;
+    let length = stream.read_u16().await? as usize;
+
+    let mut addresses = vec![0_u8; length];
+    stream.read_exact(&mut addresses).await?;
+
+    if version_command >> 4 != 0x2 {
+        return Err(proxy_error("PROXY v2 header had an unsupported version"));
+    }
+
+    match version_command & 0x0F {
+        V2_COMMAND_LOCAL => return Ok(None),
+        V2_COMMAND_PROXY => {}
+        _ => return Err(proxy_error("PROXY v2 header had an unsupported command")),
+    }
+
+    // Any trailing bytes are TLVs, which are ignored
+    match family_protocol >> 4 {
+        V2_FAMILY_INET if length >= 12 => {
+            let ip = Ipv4Addr::new(addresses[0], addresses[1], addresses[2], addresses[3]);
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+            Ok(Some(SocketAddr::new(IpAddr::V4(ip), port)))
+        }
+        V2_FAMILY_INET6 if length >= 36 => {
+            let mut ip = [0_u8; 16];
+            ip.copy_from_slice(&addresses[0..16]);
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+            Ok(Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::from(ip)), port)))
+        }
+        V2_FAMILY_INET | V2_FAMILY_INET6 => Err(proxy_error(
+            "PROXY v2 header was too short for its address family",
+        )),
+        _ => Ok(None),
+    }
+}
+
+fn proxy_error(msg: &str) -> DocumentDBError {
+    DocumentDBError::protocol_error(msg.to_string())
+}
@@ -9,18 +9,24 @@
 use std::io::{Cursor, ErrorKind};
 use std::str::FromStr;
 
-use bson::{rawdoc, RawArrayBuf, RawDocument, RawDocumentBuf};
+use ::bson::{rawdoc, RawArrayBuf, RawBsonRef, RawDocument, RawDocumentBuf};
 use tokio::io::{AsyncRead, AsyncReadExt};
 
 use crate::{
+    bson,
     context::ConnectionContext,
     error::{DocumentDBError, Result},
-    protocol::opcode::OpCode,
+    protocol::{
+        compression::{Compressor, COMPRESSED_PREAMBLE_LENGTH},
+        opcode::OpCode,
+        util::SyncLittleEndianRead,
+        MAX_MESSAGE_SIZE_BYTES,
+    },
     requests::{Request, RequestMessage, RequestType},
 };
 
 use super::header::Header;
-use super::message::{self, Message, MessageSection};
+use super::message::{self, DeleteFlags, Message, MessageSection, QueryFlags, UpdateFlags};
 
 // Read a standard message header off the client stream
 
@@ -42,10 +48,11 @@ where
 }
 
 // Given an already read header, read the remaining message bytes into a RequestMessage
+// OP_COMPRESSED messages are decompressed here, and the header is updated to carry the original opcode
 pub async fn read_request<R>(
-    header: &Header,
+    header: &mut Header,
     stream: &mut R,
-    _: &ConnectionContext,
+    connection_context: &ConnectionContext,
 ) -> Result<RequestMessage>
 where
     R: AsyncRead + Unpin + Send,
@@ -59,6 +66,14 @@ where
 
     stream.read_exact(&mut message).await?;
 
+    if header.op_code == OpCode::Compressed {
+        let (op_code, compressor, decompressed) =
+            decompress(&message, &connection_context.compressors)?;
+        header.op_code = op_code;
+        header.compressor = Some(compressor);
+        message = decompressed;
+    }
+
     Ok(RequestMessage {
         request: message,
         op_code: header.op_code,
@@ -67,16 +82,70 @@ where
     })
 }
 
+// Unwrap an OP_COMPRESSED body into the original opcode and the decompressed message
+// Only the noop compressor and the compressors negotiated for the connection are accepted
+pub fn decompress(
+    message: &[u8],
+    negotiated: &[Compressor],
+) -> Result<(OpCode, Compressor, Vec<u8>)> {
+    let mut reader = Cursor::new(message);
+    let original_op_code = OpCode::from_value(reader.read_i32_sync()?);
+    let uncompressed_size = usize::try_from(reader.read_i32_sync()?).map_err(|_| {
+        DocumentDBError::bad_value("uncompressedSize could not be converted to a usize".to_string())
+    })?;
+    let compressor_id = reader.read_u8_sync()?;
+
+    if matches!(original_op_code, OpCode::Compressed | OpCode::INVALID) {
+        return Err(DocumentDBError::bad_value(format!(
+            "Invalid original opcode for a compressed message: {:?}",
+            original_op_code
+        )));
+    }
+
+    if uncompressed_size > MAX_MESSAGE_SIZE_BYTES as usize {
+        return Err(DocumentDBError::bad_value(format!(
+            "uncompressedSize {} exceeds the maximum message size",
+            uncompressed_size
+        )));
+    }
+
+    let compressor = Compressor::from_id(compressor_id)
+        .filter(|c| *c == Compressor::Noop || negotiated.contains(c))
+        .ok_or(DocumentDBError::bad_value(format!(
+            "Compressor with id {} was not negotiated for this connection",
+            compressor_id
+        )))?;
+
+    let decompressed =
+        compressor.decompress(&message[COMPRESSED_PREAMBLE_LENGTH..], uncompressed_size)?;
+    Ok((original_op_code, compressor, decompressed))
+}
+
 // Parse a request message into a typed Request
 pub async fn parse_request<'a>(
     message: &'a RequestMessage,
     ctx: &mut ConnectionContext,
 ) -> Result<Request<'a>> {
+    ctx.requires_response = true;
+    ctx.exhaust_allowed = false;
+    ctx.legacy_cursor_reply = false;
+
     // Parse the specific message based on OpCode
     let request = match message.op_code {
-        OpCode::Query => parse_query(&message.request).await?,
+        OpCode::Query => {
+            let (request, legacy_cursor_reply) = parse_query(&message.request).await?;
+            ctx.legacy_cursor_reply = legacy_cursor_reply;
+            request
+        }
         OpCode::Msg => parse_msg(message, ctx).await?,
         OpCode::Insert => parse_insert(message).await?,
+        OpCode::Update => parse_update(message).await?,
+        OpCode::Delete => parse_delete(message).await?,
+        OpCode::GetMore => {
+            ctx.legacy_cursor_reply = true;
+            parse_get_more(message).await?
+        }
+        OpCode::KillCursors => parse_kill_cursors(message).await?,
         _ => Err(DocumentDBError::internal_error(format!(
             "Unimplemented: {:?}",
             message.op_code
@@ -86,43 +155,155 @@ pub async fn parse_request<'a>(
 }
 
 // Parse a OP_QUERY message
-async fn parse_query(message: &[u8]) -> Result<Request> {
+// The returned flag is set when the query targets a collection, and must be answered with OP_REPLY batches
+pub async fn parse_query(message: &[u8]) -> Result<(Request<'_>, bool)> {
     let mut reader = Cursor::new(message);
 
-    let _flags = reader.read_u32_le().await?;
-
-    // Parse the collection and skip the position to the end of it
-    let (collection_path, endpos) = str_from_u8_nul_utf8(&reader.get_ref()[4..])?;
-    let collection_path = collection_path.to_string();
-    reader.set_position(u64::try_from(endpos + 5).map_err(|_| {
-        DocumentDBError::internal_error("Collection length failed to convert to a u64.".to_string())
-    })?);
+    let flags = reader.read_u32_le().await?;
 
-    let _number_to_skip = reader.read_u32_le().await?;
-    let _number_to_return = reader.read_u32_le().await?;
+    let collection_path = read_collection_path(&mut reader)?;
 
-    // Parse the query into a byte array
-    let query_size = usize::try_from(reader.read_u32_le().await?).map_err(|_| {
-        DocumentDBError::bad_value("Message length could not be converted to a usize".to_string())
-    })?;
-    let pos = usize::try_from(reader.position()).map_err(|_| {
-        DocumentDBError::bad_value("Reader position could not be converted to a usize".to_string())
-    })? - 4;
-    let query_slice = &reader.get_ref()[pos..pos + query_size];
+    let number_to_skip = reader.read_i32_le().await?;
+    let number_to_return = reader.read_i32_le().await?;
 
     // Treat the bytes as a raw bson reference
-    let query = RawDocument::from_bytes(query_slice)?;
+    let (query, _) = bson::read_document_bytes(&mut reader)?;
+
+    let (db, coll) = parse_collection_path(collection_path)?;
+    if coll == "$cmd" {
+        // Commands may be wrapped in $query alongside read preference modifiers
+        let command = match query.get_document("$query") {
+            Ok(command) => command,
+            Err(_) => query,
+        };
+
+        // The legacy admin handshake is processed without a $db
+        if collection_path == "admin.$cmd" || collection_path == "$admin.$cmd" {
+            return Ok((parse_cmd(command, None).await?, false));
+        }
 
-    // OP_QUERY is only supported for commands currently
-    if collection_path == "admin.$cmd" || collection_path == "$admin.$cmd" {
-        return parse_cmd(query, None).await;
+        let mut command = command.to_raw_document_buf();
+        if command.get("$db")?.is_none() {
+            command.append("$db", db);
+        }
+        return Ok((Request::RawBuf(command_type(&command)?, command), false));
     }
 
-    Err(DocumentDBError::internal_error(
-        "OpQuery is not supported.".to_string(),
+    // The remaining bytes optionally hold the projection
+    let projection = if (reader.position() as usize) < reader.get_ref().len() {
+        Some(bson::read_document_bytes(&mut reader)?.0)
+    } else {
+        None
+    };
+
+    Ok((
+        Request::RawBuf(
+            RequestType::Find,
+            legacy_find(
+                db,
+                coll,
+                flags,
+                number_to_skip,
+                number_to_return,
+                query,
+                projection,
+            )?,
+        ),
+        true,
     ))
 }
 
+/// Translate a legacy OP_QUERY against a collection into a find command
+fn legacy_find(
+    db: &str,
+    coll: &str,
+    flags: u32,
+    number_to_skip: i32,
+    number_to_return: i32,
+    query: &RawDocument,
+    projection: Option<&RawDocument>,
+) -> Result<RawDocumentBuf> {
+    let mut find = rawdoc! { "find": coll };
+
+    // The query is either the filter itself or wraps it in $query/query alongside modifiers.
+    // A filter on a field named query is not a wrapper, so query only wraps when it holds a document.
+    let dollar_query = query.get("$query")?.is_some();
+    let wrapped = dollar_query || matches!(query.get("query")?, Some(RawBsonRef::Document(_)));
+    if wrapped {
+        for entry in query {
+            let (k, v) = entry?;
+            match k {
+                "$query" => find.append("filter", v.to_raw_bson()),
+                "query" if !dollar_query => find.append("filter", v.to_raw_bson()),
+                "$orderby" | "orderby" => find.append("sort", v.to_raw_bson()),
+                "$hint" => find.append("hint", v.to_raw_bson()),
+                "$maxTimeMS" => find.append("maxTimeMS", v.to_raw_bson()),
+                "$comment" => find.append("comment", v.to_raw_bson()),
+                "$max" => find.append("max", v.to_raw_bson()),
+                "$min" => find.append("min", v.to_raw_bson()),
+                "$returnKey" => find.append("returnKey", v.to_raw_bson()),
+                "$showDiskLoc" => find.append("showRecordId", v.to_raw_bson()),
+                _ => {}
+            }
+        }
+    } else {
+        find.append("filter", query.to_raw_document_buf());
+    }
+
+    if let Some(projection) = projection {
+        find.append("projection", projection.to_raw_document_buf());
+    }
+
+    if number_to_skip > 0 {
+        find.append("skip", number_to_skip as i64);
+    }
+
+    // A negative or single document request asks for one batch which closes the cursor
+    match number_to_return {
+        0 => {}
+        n if n < 0 || n == 1 => {
+            find.append("limit", n.unsigned_abs() as i64);
+            find.append("singleBatch", true);
+        }
+        n => find.append("batchSize", n as i64),
+    }
+
+    let query_flags = QueryFlags::from_bits_truncate(flags);
+    if query_flags.contains(QueryFlags::TAILABLE_CURSOR) {
+        find.append("tailable", true);
+    }
+    if query_flags.contains(QueryFlags::NO_CURSOR_TIMEOUT) {
+        find.append("noCursorTimeout", true);
+    }
+    if query_flags.contains(QueryFlags::AWAIT_DATA) {
+        find.append("awaitData", true);
+    }
+    if query_flags.contains(QueryFlags::PARTIAL) {
+        find.append("allowPartialResults", true);
+    }
+
+    find.append("$db", db);
+    Ok(find)
+}
+
+/// Read the nul terminated db.collection string at the reader's position, and skip past its terminator
+fn read_collection_path<'a>(reader: &mut Cursor<&'a [u8]>) -> Result<&'a str> {
+    let buffer: &'a [u8] = reader.get_ref();
+    let start = reader_offset(reader)?;
+    let (collection_path, length) = str_from_u8_nul_utf8(&buffer[start.min(buffer.len())..])?;
+    reader.set_position(u64::try_from(start + length + 1).map_err(|_| {
+        DocumentDBError::internal_error("Collection length failed to convert to a u64.".to_string())
+    })?);
+    Ok(collection_path)
+}
+
+/// The reader's position as an index into its buffer
+fn reader_offset(reader: &Cursor<&[u8]>) -> Result<usize> {
+    usize::try_from(reader.position()).map_err(|_| {
+        DocumentDBError::internal_error("Reader position failed to convert to a usize.".to_string())
+    })
+}
+
 /// Read from a byte array until a nul terminator, parse using utf-8
 pub fn str_from_u8_nul_utf8(utf8_src: &[u8]) -> Result<(&str, usize)> {
     let nul_range_end =
@@ -143,9 +324,10 @@ async fn parse_msg<'a>(
     ctx: &mut ConnectionContext,
 ) -> Result<Request<'a>> {
     let reader = Cursor::new(message.request.as_slice());
-    let msg: Message = Message::read_from_op_msg(reader, message.response_to)?;
+    let msg: Message = Message::read_from_op_msg(reader, message.request_id, message.response_to)?;
 
     ctx.requires_response = !msg._flags.contains(message::MessageFlags::MORE_TO_COME);
+    ctx.exhaust_allowed = msg._flags.contains(message::MessageFlags::EXHAUST_ALLOWED);
     match msg.sections.len() {
         0 => Err(DocumentDBError::bad_value(
             "Message had no sections".to_string(),
@@ -153,7 +335,7 @@ async fn parse_msg<'a>(
         1 => match &msg.sections[0] {
             MessageSection::Document(doc) => parse_cmd(doc, None).await,
             MessageSection::Sequence {
-                size: _,
+                _size: _,
                 _identifier: _,
                 documents: _,
             } => Err(DocumentDBError::bad_value(
@@ -182,16 +364,20 @@ async fn parse_msg<'a>(
 
 /// Parse a command document
 async fn parse_cmd<'a>(command: &'a RawDocument, extra: Option<&'a [u8]>) -> Result<Request<'a>> {
+    Ok(Request::Raw(command_type(command)?, command, extra))
+}
+
+/// Determine the request type from the command name, which is the first key of the document
+fn command_type(command: &RawDocument) -> Result<RequestType> {
     if let Some(result) = command.into_iter().next() {
         let cmd_name = result?.0;
 
         let explain = command.get_bool("explain").unwrap_or(false);
         if explain {
-            return Ok(Request::Raw(RequestType::Explain, command, extra));
+            return Ok(RequestType::Explain);
         }
 
-        let request_type = RequestType::from_str(cmd_name)?;
-        Ok(Request::Raw(request_type, command, extra))
+        RequestType::from_str(cmd_name)
     } else {
         Err(DocumentDBError::bad_value(
             "Admin command recieved without a command.".to_string(),
@@ -199,15 +385,14 @@ async fn parse_cmd<'a>(command: &'a RawDocument, extra: Option<&'a [u8]>) -> Res
     }
 }
 
+// Parse a OP_INSERT message into an insert command
 // TODO: Should not need to clone the documents and create a new RawDocumentBuf
-async fn parse_insert(message: &RequestMessage) -> Result<Request> {
-    let mut reader = Cursor::new(&message.request);
+pub async fn parse_insert(message: &RequestMessage) -> Result<Request<'_>> {
+    let mut reader = Cursor::new(message.request.as_slice());
     let flags = reader.read_i32_le().await?;
 
-    let (collection_path, endpos) = str_from_u8_nul_utf8(&reader.get_ref()[4..])?;
-
-    // Skip the flags and the nul terminator
-    let docs_slice = &reader.get_ref()[endpos + 5..];
+    let collection_path = read_collection_path(&mut reader)?;
+    let docs_slice = &reader.get_ref()[reader_offset(&reader)?..];
 
     let (db, coll) = parse_collection_path(collection_path)?;
 
@@ -222,20 +407,124 @@ async fn parse_insert(message: &RequestMessage) -> Result<Request> {
     ))
 }
 
+// Parse a OP_UPDATE message into an update command
+pub async fn parse_update(message: &RequestMessage) -> Result<Request<'_>> {
+    let mut reader = Cursor::new(message.request.as_slice());
+    let _zero = reader.read_i32_le().await?;
+
+    let collection_path = read_collection_path(&mut reader)?;
+
+    let flags = UpdateFlags::from_bits_truncate(reader.read_u32_le().await?);
+    let (selector, _) = bson::read_document_bytes(&mut reader)?;
+    let (update, _) = bson::read_document_bytes(&mut reader)?;
+
+    let (db, coll) = parse_collection_path(collection_path)?;
+
+    Ok(Request::RawBuf(
+        RequestType::Update,
+        rawdoc! {
+            "update": coll,
+            "updates": [{
+                "q": selector.to_raw_document_buf(),
+                "u": update.to_raw_document_buf(),
+                "upsert": flags.contains(UpdateFlags::UPSERT),
+                "multi": flags.contains(UpdateFlags::MULTI_UPDATE),
+            }],
+            "ordered": true,
+            "$db": db,
+        },
+    ))
+}
+
+// Parse a OP_DELETE message into a delete command
+pub async fn parse_delete(message: &RequestMessage) -> Result<Request<'_>> {
+    let mut reader = Cursor::new(message.request.as_slice());
+    let _zero = reader.read_i32_le().await?;
+
+    let collection_path = read_collection_path(&mut reader)?;
+
+    let flags = DeleteFlags::from_bits_truncate(reader.read_u32_le().await?);
+    let (selector, _) = bson::read_document_bytes(&mut reader)?;
+
+    let (db, coll) = parse_collection_path(collection_path)?;
+
+    Ok(Request::RawBuf(
+        RequestType::Delete,
+        rawdoc! {
+            "delete": coll,
+            "deletes": [{
+                "q": selector.to_raw_document_buf(),
+                "limit": if flags.contains(DeleteFlags::SINGLE_REMOVE) { 1 } else { 0 },
+            }],
+            "ordered": true,
+            "$db": db,
+        },
+    ))
+}
+
+// Parse a OP_GET_MORE message into a getMore command
+pub async fn parse_get_more(message: &RequestMessage) -> Result<Request<'_>> {
+    let mut reader = Cursor::new(message.request.as_slice());
+    let _zero = reader.read_i32_le().await?;
+
+    let collection_path = read_collection_path(&mut reader)?;
+
+    let number_to_return = reader.read_i32_le().await?;
+    let cursor_id = reader.read_i64_le().await?;
+
+    let (db, coll) = parse_collection_path(collection_path)?;
+
+    let mut get_more = rawdoc! {
+        "getMore": cursor_id,
+        "collection": coll,
+    };
+    if number_to_return > 0 {
+        get_more.append("batchSize", number_to_return as i64);
+    }
+    get_more.append("$db", db);
+
+    Ok(Request::RawBuf(RequestType::GetMore, get_more))
+}
+
+// Parse a OP_KILL_CURSORS message into a killCursors command
+// The legacy message carries no namespace, cursors are looked up by id and user only.
+pub async fn parse_kill_cursors(message: &RequestMessage) -> Result<Request<'_>> {
+    let mut reader = Cursor::new(message.request.as_slice());
+    let _zero = reader.read_i32_le().await?;
+
+    let number_of_cursor_ids = reader.read_i32_le().await?;
+    let remaining = message.request.len() - reader.position() as usize;
+    if usize::try_from(number_of_cursor_ids).map_or(true, |n| {
+        n.saturating_mul(std::mem::size_of::<i64>()) != remaining
+    }) {
+        return Err(DocumentDBError::protocol_error(format!(
+            "numberOfCursorIds {} does not match the {} bytes remaining",
+            number_of_cursor_ids, remaining
+        )));
+    }
+
+    let mut cursors = RawArrayBuf::new();
+    for _ in 0..number_of_cursor_ids {
+        cursors.push(reader.read_i64_le().await?);
+    }
+
+    Ok(Request::RawBuf(
+        RequestType::KillCursors,
+        rawdoc! {
+            "killCursors": "",
+            "cursors": cursors,
+            "$db": "admin",
+        },
+    ))
+}
+
 /// Reads a sequence of bson in bytes into an owned bson array
 fn read_documents(bytes: &'_ [u8]) -> Result<RawArrayBuf> {
     let mut result = RawArrayBuf::new();
-    let mut pos = 0;
-    while pos < bytes.len() {
-        let doc_size = i32::from_le_bytes(
-            bytes[pos..pos + 4]
-                .try_into()
-                .expect("Slice of wrong length"),
-        );
-        result.push(RawDocumentBuf::from_bytes(
-            bytes[pos..pos + (doc_size as usize)].to_vec(),
-        )?);
-        pos += doc_size as usize;
+    let mut reader = Cursor::new(bytes);
+    while (reader.position() as usize) < bytes.len() {
+        let (doc, _) = bson::read_document_bytes(&mut reader)?;
+        result.push(doc.to_raw_document_buf());
     }
     Ok(result)
 }
@@ -80,6 +80,7 @@ impl RequestInfo<'_> {
 pub enum RequestType {
     AbortTransaction,
     Aggregate,
+    Authenticate,
     BuildInfo,
     CollMod,
     CollStats,
@@ -112,7 +113,10 @@ pub enum RequestType {
     Insert,
     IsDBGrid,
     IsMaster,
+    KillAllSessions,
     KillCursors,
+    KillOp,
+    KillSessions,
     ListCollections,
     ListCommands,
     ListDatabases,
@@ -121,11 +125,13 @@ pub enum RequestType {
     Ping,
     PrepareTransaction,
     ReIndex,
+    RefreshSessions,
     RenameCollection,
     ReshardCollection,
     SaslContinue,
     SaslStart,
     ShardCollection,
+    StartSession,
     Update,
     UpdateUser,
     UsersInfo,
@@ -137,7 +143,10 @@ impl RequestType {
     pub fn handle_with_auth(&self) -> bool {
         matches!(
             &self,
-            RequestType::Logout | RequestType::SaslContinue | RequestType::SaslStart
+            RequestType::Authenticate
+                | RequestType::Logout
+                | RequestType::SaslContinue
+                | RequestType::SaslStart
         )
     }
 
@@ -156,6 +165,7 @@ impl FromStr for RequestType {
         match cmd_name {
             "abortTransaction" => Ok(RequestType::AbortTransaction),
             "aggregate" => Ok(RequestType::Aggregate),
+            "authenticate" => Ok(RequestType::Authenticate),
             "buildinfo" => Ok(RequestType::BuildInfo),
             "buildInfo" => Ok(RequestType::BuildInfo),
             "collMod" => Ok(RequestType::CollMod),
@@ -192,7 +202,10 @@ impl FromStr for RequestType {
             "isdbgrid" => Ok(RequestType::IsDBGrid),
             "ismaster" => Ok(RequestType::IsMaster),
             "isMaster" => Ok(RequestType::IsMaster),
+            "killAllSessions" => Ok(RequestType::KillAllSessions),
             "killCursors" => Ok(RequestType::KillCursors),
+            "killOp" => Ok(RequestType::KillOp),
+            "killSessions" => Ok(RequestType::KillSessions),
             "listCollections" => Ok(RequestType::ListCollections),
             "listCommands" => Ok(RequestType::ListCommands),
             "listDatabases" => Ok(RequestType::ListDatabases),
@@ -202,11 +215,13 @@ impl FromStr for RequestType {
             "prepareTransaction" => Ok(RequestType::PrepareTransaction),
             "reindex" => Ok(RequestType::ReIndex),
             "reIndex" => Ok(RequestType::ReIndex),
+            "refreshSessions" => Ok(RequestType::RefreshSessions),
             "renameCollection" => Ok(RequestType::RenameCollection),
             "reshardCollection" => Ok(RequestType::ReshardCollection),
             "saslContinue" => Ok(RequestType::SaslContinue),
             "saslStart" => Ok(RequestType::SaslStart),
             "shardCollection" => Ok(RequestType::ShardCollection),
+            "startSession" => Ok(RequestType::StartSession),
             "update" => Ok(RequestType::Update),
             "updateUser" => Ok(RequestType::UpdateUser),
             "usersInfo" => Ok(RequestType::UsersInfo),
@@ -248,6 +263,20 @@ impl<'a> Request<'a> {
         }
     }
 
+    /// Whether the request is a write which the driver may retry, one with a session and
+    /// transaction number outside of a multi-document transaction.
+    pub fn is_retryable_write(&self) -> bool {
+        matches!(
+            self.request_type(),
+            RequestType::Insert
+                | RequestType::Update
+                | RequestType::Delete
+                | RequestType::FindAndModify
+        ) && matches!(self.document().get("lsid"), Ok(Some(_)))
+            && matches!(self.document().get("txnNumber"), Ok(Some(_)))
+            && matches!(self.document().get("autocommit"), Ok(None))
+    }
+
     pub fn extra(&'a self) -> Option<&'a [u8]> {
         match self {
             Request::Raw(_, _, extra) => *extra,
@@ -36,8 +36,17 @@ pub struct CommandError {
     /// A description of the error that occurred.
     #[serde(rename = "errmsg", default = "String::new")]
     pub message: String,
+
+    /// Labels which tell drivers how to handle the error, such as whether the write may be retried.
+    #[serde(rename = "errorLabels", default, skip_serializing_if = "Vec::is_empty")]
+    pub error_labels: Vec<String>,
 }
 
+/// Error codes on which drivers retry a retryable write.
+const RETRYABLE_WRITE_ERROR_CODES: [i32; 12] = [
+    6, 7, 89, 91, 189, 262, 9001, 10107, 11600, 11602, 13435, 13436,
+];
+
 impl CommandError {
     pub fn new(code: i32, code_name: String, msg: String) -> Self {
         CommandError {
@@ -45,6 +54,14 @@ impl CommandError {
             code,
             code_name,
             message: msg,
+            error_labels: Vec::new(),
+        }
+    }
+
+    /// Labels the error of a retryable write which the driver may retry.
+    pub fn add_retryable_write_label(&mut self) {
+        if RETRYABLE_WRITE_ERROR_CODES.contains(&self.code) {
+            self.error_labels.push("RetryableWriteError".to_string());
         }
     }
 
@@ -8,10 +8,15 @@
 
 use crate::{
     context::ConnectionContext,
-    error::DocumentDBError,
-    protocol::{header::Header, opcode::OpCode},
+    error::{DocumentDBError, ErrorCode},
+    protocol::{
+        compression::COMPRESSED_PREAMBLE_LENGTH,
+        header::Header,
+        message::{op_msg_checksum, MessageFlags, ReplyFlags},
+        opcode::OpCode,
+    },
 };
-use bson::{to_raw_document_buf, RawDocument};
+use bson::{rawdoc, spec::ElementType, to_raw_document_buf, RawDocument};
 use tokio::{
     io::{AsyncRead, AsyncWrite, AsyncWriteExt},
     net::TcpStream,
@@ -51,28 +56,12 @@ where
 
         // Query is responded to with Reply
         OpCode::Query => {
-            // Write the header
-            let header = Header {
-                // Total size of the response is the bytes + standard header + reply header
-                length: (response.as_bytes().len() + Header::LENGTH + 20) as i32,
-                request_id: header.request_id,
-                response_to: header.request_id,
-                op_code: OpCode::Reply,
-                activity_id: header.activity_id.clone(),
-            };
-            header.write_to(stream).await?;
-
-            stream.write_i32_le(0).await?; // Response flags
-            stream.write_i64_le(0).await?; // Cursor Id
-            stream.write_i32_le(0).await?; // startingFrom
-            stream.write_i32_le(1).await?; // numberReturned
-
-            stream.write_all(response.as_bytes()).await?;
-            Ok(())
+            let message = op_reply_message(ReplyFlags::empty(), 0, 0, &[response]);
+            write_reply(header, OpCode::Reply, message, stream).await
         }
 
-        // Insert has no response
-        OpCode::Insert => Ok(()),
+        // Legacy writes and killCursors have no response
+        OpCode::Insert | OpCode::Update | OpCode::Delete | OpCode::KillCursors => Ok(()),
         _ => Err(DocumentDBError::internal_error(format!(
             "Unexpected response opcode: {:?}",
             header.op_code
@@ -82,6 +71,96 @@ where
     Ok(())
 }
 
+/// Writes the batch of a cursor response as the documents of an OP_REPLY, for legacy OP_QUERY and OP_GET_MORE.
+/// Returns the cursor id and the number of documents returned.
+pub async fn write_legacy_reply<R>(
+    header: &Header,
+    response: &RawDocument,
+    starting_from: i32,
+    stream: &mut R,
+) -> Result<(i64, i32), DocumentDBError>
+where
+    R: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    let cursor = response
+        .get_document("cursor")
+        .map_err(DocumentDBError::pg_response_invalid)?;
+    let cursor_id = cursor
+        .get_i64("id")
+        .map_err(DocumentDBError::pg_response_invalid)?;
+    let batch = match cursor.get_array("firstBatch") {
+        Ok(batch) => batch,
+        Err(_) => cursor
+            .get_array("nextBatch")
+            .map_err(DocumentDBError::pg_response_invalid)?,
+    };
+
+    let mut documents = Vec::new();
+    for document in batch {
+        documents.push(
+            document?
+                .as_document()
+                .ok_or(DocumentDBError::internal_error(
+                    "Cursor batch contained a value which is not a document".to_string(),
+                ))?,
+        );
+    }
+
+    let message = op_reply_message(
+        ReplyFlags::AWAIT_CAPABLE,
+        cursor_id,
+        starting_from,
+        &documents,
+    );
+    write_reply(header, OpCode::Reply, message, stream).await?;
+    stream.flush().await?;
+    Ok((cursor_id, documents.len() as i32))
+}
+
+/// Writes an error as an OP_REPLY for legacy OP_QUERY and OP_GET_MORE requests.
+pub async fn write_legacy_error<R>(
+    header: &Header,
+    error: &CommandError,
+    stream: &mut R,
+) -> Result<(), DocumentDBError>
+where
+    R: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    let message =
+        if header.op_code == OpCode::GetMore && error.code == ErrorCode::CursorNotFound as i32 {
+            op_reply_message(ReplyFlags::CURSOR_NOT_FOUND, 0, 0, &[])
+        } else {
+            let error_doc = rawdoc! {
+                "$err": error.message.as_str(),
+                "code": error.code,
+                "ok": error.ok,
+            };
+            op_reply_message(ReplyFlags::QUERY_FAILURE, 0, 0, &[&error_doc])
+        };
+    write_reply(header, OpCode::Reply, message, stream).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Builds an OP_REPLY message, with room reserved for the header.
+fn op_reply_message(
+    flags: ReplyFlags,
+    cursor_id: i64,
+    starting_from: i32,
+    documents: &[&RawDocument],
+) -> Vec<u8> {
+    let documents_length: usize = documents.iter().map(|d| d.as_bytes().len()).sum();
+    let mut body = reply_buffer(20 + documents_length);
+    body.extend_from_slice(&flags.bits().to_le_bytes()); // Response flags
+    body.extend_from_slice(&cursor_id.to_le_bytes()); // Cursor Id
+    body.extend_from_slice(&starting_from.to_le_bytes()); // startingFrom
+    body.extend_from_slice(&(documents.len() as i32).to_le_bytes()); // numberReturned
+    for document in documents {
+        body.extend_from_slice(document.as_bytes());
+    }
+    body
+}
+
 /// Serializes the Message to bytes and writes them to `writer`.
 pub async fn write_message<R>(
     header: &Header,
@@ -91,28 +170,197 @@ pub async fn write_message<R>(
 where
     R: AsyncRead + AsyncWrite + Unpin + Send,
 {
-    let total_length = Header::LENGTH
-        + std::mem::size_of::<u32>()
-        + std::mem::size_of::<u8>()
-        + response.as_bytes().len(); // Message payload + status code
+    write_message_with_flags(header, response, MessageFlags::empty(), writer).await
+}
 
-    let header = Header {
-        length: total_length as i32,
-        request_id: header.request_id,
-        response_to: header.request_id,
-        op_code: OpCode::Msg,
-        activity_id: header.activity_id.clone(),
-    };
-    header.write_to(writer).await?;
+/// Serializes the Message to bytes with the provided flags, such as MORE_TO_COME for exhaust replies.
+pub(crate) async fn write_message_with_flags<R>(
+    header: &Header,
+    response: &RawDocument,
+    mut flags: MessageFlags,
+    writer: &mut R,
+) -> Result<(), DocumentDBError>
+where
+    R: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    let mut body = reply_buffer(
+        2 * std::mem::size_of::<u32>() + 2 * std::mem::size_of::<u8>() + response.as_bytes().len(),
+    );
 
     // Write Flags
-    writer.write_u32_le(0).await?;
+    if header.response_checksum {
+        flags |= MessageFlags::CHECKSUM_PRESENT;
+    }
+    body.extend_from_slice(&flags.bits().to_le_bytes());
+
+    // Write payload type + section, moving the cursor batch into a document sequence when enabled
+    if !(header.response_document_sequences && write_cursor_batch_sections(&mut body, response)?) {
+        body.push(0);
+        body.extend_from_slice(response.as_bytes());
+    }
+
+    if header.response_checksum {
+        let checksum = op_msg_checksum(
+            header.request_id,
+            header.request_id,
+            &body[Header::LENGTH..],
+        );
+        body.extend_from_slice(&checksum.to_le_bytes());
+    }
+
+    write_reply(header, OpCode::Msg, body, writer).await
+}
 
-    // Write payload type + section
-    writer.write_u8(0).await?;
+/// The byte offsets of an element within its enclosing document.
+struct ElementRange {
+    start: usize,
+    value_start: usize,
+    end: usize,
+    element_type: ElementType,
+}
 
-    writer.write_all(response.as_bytes()).await?;
+/// Finds the element named `key` at the top level of `document`.
+fn find_element(
+    document: &RawDocument,
+    key: &str,
+) -> Result<Option<ElementRange>, DocumentDBError> {
+    let mut start = std::mem::size_of::<i32>();
+    for element in document.iter_elements() {
+        let element = element?;
+        // An element is its type byte, the nul terminated key and the value
+        let value_start = start + 1 + element.key().len() + 1;
+        let end = value_start + element.len();
+        if element.key() == key {
+            return Ok(Some(ElementRange {
+                start,
+                value_start,
+                end,
+                element_type: element.element_type(),
+            }));
+        }
+        start = end;
+    }
+    Ok(None)
+}
 
+/// Writes a cursor response as a kind-0 section holding the response without its batch, followed by a
+/// kind-1 document sequence holding the batch. Both sections are copied straight out of the response bytes,
+/// which for backend responses are the PgResponse rows, so no intermediate document is built.
+/// Returns false without writing anything when the response has no cursor batch.
+fn write_cursor_batch_sections(
+    body: &mut Vec<u8>,
+    response: &RawDocument,
+) -> Result<bool, DocumentDBError> {
+    let bytes = response.as_bytes();
+    let Some(cursor) = find_element(response, "cursor")?
+        .filter(|cursor| cursor.element_type == ElementType::EmbeddedDocument)
+    else {
+        return Ok(false);
+    };
+    let cursor_document = RawDocument::from_bytes(&bytes[cursor.value_start..cursor.end])?;
+
+    let (identifier, batch) = match find_element(cursor_document, "firstBatch")? {
+        Some(batch) => ("cursor.firstBatch", batch),
+        None => match find_element(cursor_document, "nextBatch")? {
+            Some(batch) => ("cursor.nextBatch", batch),
+            None => return Ok(false),
+        },
+    };
+    if batch.element_type != ElementType::Array {
+        return Ok(false);
+    }
+
+    let batch_document =
+        RawDocument::from_bytes(&cursor_document.as_bytes()[batch.value_start..batch.end])?;
+    let mut documents = Vec::new();
+    for entry in batch_document {
+        let (_, value) = entry?;
+        documents.push(value.as_document().ok_or(DocumentDBError::internal_error(
+            "Cursor batch contained a value which is not a document".to_string(),
+        ))?);
+    }
+
+    // The batch element is cut out of the cursor, so both enclosing document lengths shrink by its size
+    let removed = batch.end - batch.start;
+    let batch_start = cursor.value_start + batch.start;
+    let batch_end = cursor.value_start + batch.end;
+    body.push(0);
+    body.extend_from_slice(&((bytes.len() - removed) as i32).to_le_bytes());
+    body.extend_from_slice(&bytes[std::mem::size_of::<i32>()..cursor.value_start]);
+    body.extend_from_slice(&((cursor.end - cursor.value_start - removed) as i32).to_le_bytes());
+    body.extend_from_slice(&bytes[cursor.value_start + std::mem::size_of::<i32>()..batch_start]);
+    body.extend_from_slice(&bytes[batch_end..]);
+
+    let documents_length: usize = documents.iter().map(|d| d.as_bytes().len()).sum();
+    let size = std::mem::size_of::<i32>() + identifier.len() + 1 + documents_length;
+    body.push(1);
+    body.extend_from_slice(&(size as i32).to_le_bytes());
+    body.extend_from_slice(identifier.as_bytes());
+    body.push(0);
+    for document in documents {
+        body.extend_from_slice(document.as_bytes());
+    }
+    Ok(true)
+}
+
+/// Allocates the buffer for a reply, with the first bytes reserved for the header which is filled in by write_reply.
+fn reply_buffer(body_capacity: usize) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(Header::LENGTH + body_capacity);
+    buffer.resize(Header::LENGTH, 0);
+    buffer
+}
+
+/// Completes a reply built by reply_buffer with its header and writes it to `writer` in a single write,
+/// so a reply costs one syscall and one TLS record rather than one per field.
+/// The reply is wrapped in an OP_COMPRESSED message when the request was compressed.
+async fn write_reply<R>(
+    request_header: &Header,
+    op_code: OpCode,
+    mut message: Vec<u8>,
+    writer: &mut R,
+) -> Result<(), DocumentDBError>
+where
+    R: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    match request_header.compressor {
+        Some(compressor) => {
+            let body = &message[Header::LENGTH..];
+            let compressed = compressor.compress(body)?;
+            let header = Header {
+                length: (Header::LENGTH + COMPRESSED_PREAMBLE_LENGTH + compressed.len()) as i32,
+                request_id: request_header.request_id,
+                response_to: request_header.request_id,
+                op_code: OpCode::Compressed,
+                activity_id: request_header.activity_id.clone(),
+                compressor: None,
+                response_checksum: false,
+                response_document_sequences: false,
+            };
+
+            let mut compressed_message = Vec::with_capacity(header.length as usize);
+            compressed_message.extend_from_slice(&header.to_bytes());
+            compressed_message.extend_from_slice(&(op_code as i32).to_le_bytes());
+            compressed_message.extend_from_slice(&(body.len() as i32).to_le_bytes());
+            compressed_message.push(compressor.id());
+            compressed_message.extend_from_slice(&compressed);
+            message = compressed_message;
+        }
+        None => {
+            let header = Header {
+                length: message.len() as i32,
+                request_id: request_header.request_id,
+                response_to: request_header.request_id,
+                op_code,
+                activity_id: request_header.activity_id.clone(),
+                compressor: None,
+                response_checksum: false,
+                response_document_sequences: false,
+            };
+            message[..Header::LENGTH].copy_from_slice(&header.to_bytes());
+        }
+    }
+
+    writer.write_all(&message).await?;
     Ok(())
 }
 
@@ -126,9 +374,7 @@ pub async fn write_error(
         .map_err(|e| {
         DocumentDBError::internal_error(format!("Failed to serialize error with: {}", e))
     })?;
-    write_response(header, &response, stream).await?;
-    stream.flush().await?;
-    Ok(())
+    write_response(header, &response, stream).await
 }
 
 pub async fn write_error_without_header<R>(
@@ -150,8 +396,9 @@ where
         response_to: 0,
         op_code: OpCode::Msg,
         activity_id: Uuid::default().to_string(),
+        compressor: None,
+        response_checksum: false,
+        response_document_sequences: false,
     };
-    write_response(&header, &response, stream).await?;
-    stream.flush().await?;
-    Ok(())
+    write_response(&header, &response, stream).await
 }
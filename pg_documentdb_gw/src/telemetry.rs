@@ -6,7 +6,9 @@
  *-------------------------------------------------------------------------
  */
 
-use crate::context::ConnectionContext;
+use std::time::Duration;
+
+use crate::context::{AuthThrottleKey, ConnectionContext};
 use crate::protocol::header::Header;
 use crate::requests::{Request, RequestInfo};
 use crate::responses::{CommandError, Response};
@@ -28,6 +30,15 @@ pub trait TelemetryProvider: Send + Sync + DynClone {
         _: String,
         _: &mut RequestInfo<'_>,
     );
+
+    // Emits an event when repeated authentication failures lock out a client address or user
+    async fn emit_auth_lockout_event(
+        &self,
+        _: &ConnectionContext,
+        _: &AuthThrottleKey,
+        _: Duration,
+    ) {
+    }
 }
 
 clone_trait_object!(TelemetryProvider);
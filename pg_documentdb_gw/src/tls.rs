@@ -0,0 +1,235 @@
+/*-------------------------------------------------------------------------
+ * Copyright (c) Microsoft Corporation.  All rights reserved.
+ *
+ * src/tls.rs
+ *
+ *-------------------------------------------------------------------------
+ */
+
+use std::{sync::Arc, time::Duration, time::SystemTime};
+
+use arc_swap::ArcSwap;
+use openssl::{
+    pkcs12::Pkcs12,
+    ssl::{
+        Ssl, SslContext, SslContextBuilder, SslFiletype, SslMethod, SslOptions, SslVerifyMode,
+        SslVersion,
+    },
+    x509::X509Name,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    configuration::{CertificateOptions, ClientCertificateMode},
+    error::{DocumentDBError, Result},
+};
+
+/// Identifies the contents of a certificate file by its modification time and size.
+type FileVersion = Option<(SystemTime, u64)>;
+
+/// Holds the TLS context shared by all connections.
+/// The context is rebuilt when the files in the certificate options change, and swapped in atomically
+/// so that in flight handshakes keep the context they started with.
+pub struct TlsProvider {
+    context: ArcSwap<SslContext>,
+}
+
+impl TlsProvider {
+    pub async fn new(
+        certificate_options: CertificateOptions,
+        refresh_interval: u32,
+        token: CancellationToken,
+    ) -> Result<Arc<Self>> {
+        let versions = file_versions(&certificate_options).await;
+        let provider = Arc::new(TlsProvider {
+            context: ArcSwap::from_pointee(build_context_blocking(&certificate_options).await?),
+        });
+
+        Self::start_certificate_refresh_thread(
+            provider.clone(),
+            certificate_options,
+            versions,
+            refresh_interval,
+            token,
+        );
+        Ok(provider)
+    }
+
+    /// Creates the per connection TLS state from the current context.
+    pub fn ssl(&self) -> Result<Ssl> {
+        Ok(Ssl::new(&self.context.load())?)
+    }
+
+    fn start_certificate_refresh_thread(
+        provider: Arc<TlsProvider>,
+        certificate_options: CertificateOptions,
+        mut versions: Vec<FileVersion>,
+        refresh_interval: u32,
+        token: CancellationToken,
+    ) {
+        tokio::spawn(async move {
+            // A zero period would make the interval panic
+            let mut interval =
+                tokio::time::interval(Duration::from_secs(refresh_interval.max(1) as u64));
+            interval.tick().await;
+
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = token.cancelled() => break,
+                }
+
+                let current = file_versions(&certificate_options).await;
+                if current == versions {
+                    continue;
+                }
+
+                // A failed build keeps the previous certificate, and is retried on the next tick
+                // since a rotation may have been observed half way through.
+                match build_context_blocking(&certificate_options).await {
+                    Ok(context) => {
+                        provider.context.store(Arc::new(context));
+                        versions = current;
+                        log::info!(
+                            "Reloaded TLS certificate from {}",
+                            certificate_options.file_path
+                        );
+                    }
+                    Err(e) => log::error!(
+                        "Failed to reload TLS certificate, keeping the previous certificate: {}",
+                        e
+                    ),
+                }
+            }
+        });
+    }
+}
+
+/// Builds the TLS context on the blocking pool, since loading the certificate, key and CA files reads from disk.
+async fn build_context_blocking(certificate_options: &CertificateOptions) -> Result<SslContext> {
+    let certificate_options = certificate_options.clone();
+    tokio::task::spawn_blocking(move || build_context(&certificate_options))
+        .await
+        .map_err(|e| {
+            DocumentDBError::internal_error(format!("Failed to build the TLS context: {}", e))
+        })?
+}
+
+fn build_context(certificate_options: &CertificateOptions) -> Result<SslContext> {
+    let mut builder = SslContextBuilder::new(SslMethod::tls())?;
+
+    match certificate_options
+        .cert_type
+        .to_ascii_lowercase()
+        .replace('_', "")
+        .as_str()
+    {
+        "pemfile" | "pem" => {
+            builder.set_private_key_file(&certificate_options.key_file_path, SslFiletype::PEM)?;
+            builder.set_certificate_chain_file(&certificate_options.file_path)?;
+        }
+        "pkcs12" | "pfx" => {
+            let pkcs12 = Pkcs12::from_der(&std::fs::read(&certificate_options.file_path)?)?
+                .parse2(certificate_options.password.as_deref().unwrap_or(""))?;
+            let (Some(key), Some(cert)) = (pkcs12.pkey, pkcs12.cert) else {
+                return Err(DocumentDBError::internal_error(
+                    "PKCS#12 file must contain a private key and a certificate".to_string(),
+                ));
+            };
+            builder.set_private_key(&key)?;
+            builder.set_certificate(&cert)?;
+            for chain_cert in pkcs12.ca.into_iter().flatten() {
+                builder.add_extra_chain_cert(chain_cert)?;
+            }
+        }
+        _ => {
+            return Err(DocumentDBError::internal_error(format!(
+                "Unsupported certificate type: {}",
+                certificate_options.cert_type
+            )))
+        }
+    }
+    builder.check_private_key()?;
+
+    if let Some(ca_path) = certificate_options.ca_path.as_deref() {
+        builder.set_ca_file(ca_path)?;
+    }
+
+    // Without a configured minimum, TLS 1.0 and 1.1 stay disabled as before. Other defaults are OpenSSL's own.
+    match certificate_options.min_tls_version.as_deref() {
+        Some(version) => builder.set_min_proto_version(Some(parse_tls_version(version)?))?,
+        None => {
+            builder.set_options(SslOptions::NO_TLSV1 | SslOptions::NO_TLSV1_1);
+        }
+    }
+    if let Some(version) = certificate_options.max_tls_version.as_deref() {
+        builder.set_max_proto_version(Some(parse_tls_version(version)?))?;
+    }
+    if let Some(cipher_list) = certificate_options.cipher_list.as_deref() {
+        builder.set_cipher_list(cipher_list)?;
+    }
+    if let Some(cipher_suites) = certificate_options.cipher_suites.as_deref() {
+        builder.set_ciphersuites(cipher_suites)?;
+    }
+
+    match certificate_options.client_certificate_mode {
+        ClientCertificateMode::None => builder.set_verify(SslVerifyMode::NONE),
+        mode => {
+            // Clients are told which CAs are accepted, and their certificates are verified against them
+            let ca_path =
+                certificate_options
+                    .ca_path
+                    .as_deref()
+                    .ok_or(DocumentDBError::internal_error(
+                        "Client certificate verification requires a CA file".to_string(),
+                    ))?;
+            builder.set_client_ca_list(X509Name::load_client_ca_file(ca_path)?);
+            builder.set_verify(if mode == ClientCertificateMode::Require {
+                SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT
+            } else {
+                SslVerifyMode::PEER
+            });
+        }
+    }
+
+    Ok(builder.build())
+}
+
+/// Parses a protocol version such as "TLS1.2" or "TLSv1.2".
+pub fn parse_tls_version(version: &str) -> Result<SslVersion> {
+    let normalized: String = version
+        .to_ascii_uppercase()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() && *c != 'V')
+        .collect();
+    match normalized.as_str() {
+        "TLS1" | "TLS10" => Ok(SslVersion::TLS1),
+        "TLS11" => Ok(SslVersion::TLS1_1),
+        "TLS12" => Ok(SslVersion::TLS1_2),
+        "TLS13" => Ok(SslVersion::TLS1_3),
+        _ => Err(DocumentDBError::internal_error(format!(
+            "Unsupported TLS version: {}",
+            version
+        ))),
+    }
+}
+
+async fn file_versions(certificate_options: &CertificateOptions) -> Vec<FileVersion> {
+    let mut versions = Vec::new();
+    for path in [
+        Some(&certificate_options.file_path),
+        Some(&certificate_options.key_file_path),
+        certificate_options.ca_path.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        versions.push(
+            tokio::fs::metadata(path)
+                .await
+                .ok()
+                .and_then(|metadata| Some((metadata.modified().ok()?, metadata.len()))),
+        );
+    }
+    versions
+}
@@ -0,0 +1,169 @@
+use std::{net::Ipv4Addr, time::Duration};
+
+use documentdb_gateway::{
+    configuration::{AuthThrottleOptions, DocumentDBSetupConfiguration},
+    context::{AuthThrottle, AuthThrottleKey},
+};
+
+fn throttle(options: AuthThrottleOptions) -> AuthThrottle {
+    AuthThrottle::new(&DocumentDBSetupConfiguration {
+        auth_throttle_options: Some(options),
+        ..Default::default()
+    })
+}
+
+fn options() -> AuthThrottleOptions {
+    AuthThrottleOptions {
+        enabled: true,
+        max_failures_per_ip: 6,
+        max_failures_per_user: 4,
+        failure_window_secs: 60,
+        failures_before_backoff: 2,
+        initial_backoff_millis: 100,
+        max_backoff_secs: 1,
+        lockout_secs: 10,
+    }
+}
+
+fn ip() -> AuthThrottleKey {
+    AuthThrottleKey::Ip(Ipv4Addr::new(192, 0, 2, 7).into())
+}
+
+fn user() -> AuthThrottleKey {
+    AuthThrottleKey::User("app_user".to_string())
+}
+
+#[tokio::test(start_paused = true)]
+async fn backs_off_after_allowed_failures() {
+    let throttle = throttle(options());
+
+    // The first failure is allowed without a backoff
+    assert_eq!(throttle.record_failure(ip()).await, None);
+    assert!(throttle.check(&ip()).await.is_ok());
+
+    // Later failures back off for a doubling duration
+    assert_eq!(throttle.record_failure(ip()).await, None);
+    assert!(throttle.check(&ip()).await.is_err());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(throttle.check(&ip()).await.is_ok());
+
+    assert_eq!(throttle.record_failure(ip()).await, None);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(throttle.check(&ip()).await.is_err());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(throttle.check(&ip()).await.is_ok());
+}
+
+#[tokio::test(start_paused = true)]
+async fn caps_backoff() {
+    let throttle = throttle(AuthThrottleOptions {
+        max_failures_per_ip: 100,
+        ..options()
+    });
+    for _ in 0..10 {
+        throttle.record_failure(ip()).await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    assert!(throttle.check(&ip()).await.is_ok());
+}
+
+#[tokio::test(start_paused = true)]
+async fn ignores_failures_while_blocked() {
+    let throttle = throttle(options());
+    throttle.record_failure(ip()).await;
+    throttle.record_failure(ip()).await;
+
+    // Rejected attempts do not extend the backoff
+    throttle.record_failure(ip()).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(throttle.check(&ip()).await.is_ok());
+}
+
+#[tokio::test(start_paused = true)]
+async fn locks_out_at_failure_limit() {
+    let throttle = throttle(options());
+    for _ in 0..3 {
+        assert_eq!(throttle.record_failure(user()).await, None);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    assert_eq!(
+        throttle.record_failure(user()).await,
+        Some(Duration::from_secs(10))
+    );
+
+    tokio::time::sleep(Duration::from_secs(9)).await;
+    assert!(throttle.check(&user()).await.is_err());
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert!(throttle.check(&user()).await.is_ok());
+
+    // The lockout starts a new count
+    assert_eq!(throttle.record_failure(user()).await, None);
+}
+
+#[tokio::test(start_paused = true)]
+async fn forgets_failures_after_window() {
+    let throttle = throttle(options());
+    for _ in 0..3 {
+        throttle.record_failure(user()).await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    // Failures older than the window no longer count towards the lockout or backoff
+    tokio::time::sleep(Duration::from_secs(60)).await;
+    assert_eq!(throttle.record_failure(user()).await, None);
+    assert!(throttle.check(&user()).await.is_ok());
+    for _ in 0..2 {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(throttle.record_failure(user()).await, None);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn success_clears_failures() {
+    let throttle = throttle(options());
+    throttle.record_failure(user()).await;
+    throttle.record_failure(user()).await;
+    assert!(throttle.check(&user()).await.is_err());
+
+    throttle.record_success(&user()).await;
+    assert!(throttle.check(&user()).await.is_ok());
+}
+
+#[tokio::test(start_paused = true)]
+async fn counts_keys_separately() {
+    let throttle = throttle(options());
+    throttle.record_failure(ip()).await;
+    throttle.record_failure(ip()).await;
+    assert!(throttle.check(&ip()).await.is_err());
+
+    assert!(throttle.check(&user()).await.is_ok());
+    assert!(throttle
+        .check(&AuthThrottleKey::UnixPeer(1000))
+        .await
+        .is_ok());
+    assert!(throttle
+        .check(&AuthThrottleKey::Ip(Ipv4Addr::LOCALHOST.into()))
+        .await
+        .is_ok());
+}
+
+#[tokio::test(start_paused = true)]
+async fn disabled_throttle_never_blocks() {
+    let throttle = throttle(AuthThrottleOptions {
+        enabled: false,
+        ..options()
+    });
+    for _ in 0..10 {
+        assert_eq!(throttle.record_failure(user()).await, None);
+    }
+    assert!(throttle.check(&user()).await.is_ok());
+}
+
+#[tokio::test(start_paused = true)]
+async fn disabled_by_default() {
+    let throttle = AuthThrottle::new(&DocumentDBSetupConfiguration::default());
+    for _ in 0..10 {
+        assert_eq!(throttle.record_failure(user()).await, None);
+    }
+    assert!(throttle.check(&user()).await.is_ok());
+}
@@ -1,12 +1,17 @@
 use std::sync::Arc;
-use std::{backtrace::Backtrace, env, sync::Once, thread, time::Duration};
+use std::{backtrace::Backtrace, env, pin::Pin, sync::Once, thread, time::Duration};
+
+use bson::{rawdoc, spec::BinarySubtype, Binary, RawDocument, RawDocumentBuf};
 
 use documentdb_gateway::configuration::{
     DocumentDBSetupConfiguration, PgConfiguration, SetupConfiguration,
 };
 use documentdb_gateway::error::Result;
-use documentdb_gateway::postgres::{create_query_catalog, Pool};
-use documentdb_gateway::{get_service_context, populate_ssl_certificates, QueryCatalog};
+use documentdb_gateway::postgres::{create_query_catalog, ConnectionPool};
+use documentdb_gateway::protocol::{header::Header, opcode::OpCode, reader};
+use documentdb_gateway::{
+    get_service_context, populate_ssl_certificates, QueryCatalog, AUTHENTICATION_MAX_CONNECTIONS,
+};
 
 use documentdb_gateway::run_server;
 use mongodb::options::{Tls, TlsOptions};
@@ -14,7 +19,13 @@ use mongodb::{
     options::{AuthMechanism, ClientOptions, Credential, ServerAddress},
     Client, Database,
 };
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
 use simple_logger::SimpleLogger;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tokio_openssl::SslStream;
 use tokio_postgres::{error::SqlState, NoTls};
 use tokio_util::sync::CancellationToken;
 
@@ -48,7 +59,7 @@ async fn run(config: DocumentDBSetupConfiguration) {
     let query_catalog = create_query_catalog();
     let postgres_system_user = config.postgres_system_user();
     let system_pool = Arc::new(
-        Pool::new_with_user(
+        ConnectionPool::new_with_user(
             &config,
             &query_catalog,
             &postgres_system_user,
@@ -58,6 +69,17 @@ async fn run(config: DocumentDBSetupConfiguration) {
         )
         .expect("Failed to create system pool"),
     );
+    let authentication_pool = Arc::new(
+        ConnectionPool::new_with_user(
+            &config,
+            &query_catalog,
+            &postgres_system_user,
+            None,
+            format!("{}-PreAuthRequests", config.application_name()),
+            AUTHENTICATION_MAX_CONNECTIONS,
+        )
+        .expect("Failed to create authentication pool"),
+    );
 
     let certificate_options = if let Some(co) = config.certificate_options.clone() {
         co
@@ -75,6 +97,7 @@ async fn run(config: DocumentDBSetupConfiguration) {
         dynamic_configuration,
         query_catalog,
         system_pool,
+        authentication_pool,
     )
     .await
     .unwrap();
@@ -95,8 +118,8 @@ pub fn configuration() -> DocumentDBSetupConfiguration {
         node_host_name: "localhost".to_string(),
         blocked_role_prefixes: Vec::new(),
         gateway_listen_port: Some(10260),
-        allow_transaction_snapshot: false,
-        enforce_ssl_tcp: true,
+        allow_transaction_snapshot: Some(false),
+        enforce_ssl_tcp: Some(true),
         postgres_system_user: Some(
             std::env::var("PostgresSystemUser").unwrap_or("cosmosdev".to_string()),
         ),
@@ -105,9 +128,13 @@ pub fn configuration() -> DocumentDBSetupConfiguration {
 }
 
 pub fn get_client() -> Client {
+    get_client_with_credentials("test", "test")
+}
+
+pub fn get_client_with_credentials(user: &str, pass: &str) -> Client {
     let credential = Credential::builder()
-        .username("test".to_string())
-        .password("test".to_string())
+        .username(user.to_string())
+        .password(pass.to_string())
         .mechanism(AuthMechanism::ScramSha256)
         .build();
 
@@ -156,7 +183,8 @@ pub async fn initialize() -> Client {
     get_client()
 }
 
-pub async fn create_user(user: &str, pass: &str, query_catalog: &QueryCatalog) -> Result<()> {
+// Connects to the backend directly, bypassing the gateway
+pub async fn postgres_client() -> tokio_postgres::Client {
     let (client, connection) = tokio_postgres::Config::new()
         .host("localhost")
         .port(9712)
@@ -165,6 +193,11 @@ pub async fn create_user(user: &str, pass: &str, query_catalog: &QueryCatalog) -
         .await
         .unwrap();
     tokio::spawn(connection);
+    client
+}
+
+pub async fn create_user(user: &str, pass: &str, query_catalog: &QueryCatalog) -> Result<()> {
+    let client = postgres_client().await;
 
     let statement = query_catalog.create_db_user(user, pass);
     match client.batch_execute(&statement).await {
@@ -195,7 +228,7 @@ pub async fn create_user(user: &str, pass: &str, query_catalog: &QueryCatalog) -
         ))
         .await
         .unwrap()
-        .get(0)
+        .first()
         .unwrap()
     {
         log::info!("Test can create: {:?}", result.get("rolcreaterole"));
@@ -218,3 +251,91 @@ pub async fn setup_db(client: &Client, db: &str) -> Database {
     db.drop().await.unwrap();
     db
 }
+
+#[allow(dead_code)]
+pub const MORE_TO_COME: u32 = 1 << 1;
+
+// Opens a TLS connection to the gateway without a driver, authenticated as the test user with PLAIN
+#[allow(dead_code)]
+pub async fn connect_raw() -> SslStream<TcpStream> {
+    let tcp = TcpStream::connect("127.0.0.1:10260").await.unwrap();
+    let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+    connector.set_verify(SslVerifyMode::NONE);
+    let ssl = connector
+        .build()
+        .configure()
+        .unwrap()
+        .into_ssl("localhost")
+        .unwrap();
+
+    let mut stream = SslStream::new(ssl, tcp).unwrap();
+    Pin::new(&mut stream).connect().await.unwrap();
+
+    run_raw_command(
+        &mut stream,
+        1,
+        &rawdoc! {
+            "saslStart": 1,
+            "mechanism": "PLAIN",
+            "payload": Binary { subtype: BinarySubtype::Generic, bytes: b"\0test\0test".to_vec() },
+            "$db": "$external",
+        },
+    )
+    .await;
+    stream
+}
+
+// Sends an OP_MSG holding the command as its single document section
+#[allow(dead_code)]
+pub async fn send_op_msg(
+    stream: &mut SslStream<TcpStream>,
+    request_id: i32,
+    flags: u32,
+    command: &RawDocument,
+) {
+    let length = Header::LENGTH + 5 + command.as_bytes().len();
+    let mut message = Vec::with_capacity(length);
+    message.extend_from_slice(&(length as i32).to_le_bytes());
+    message.extend_from_slice(&request_id.to_le_bytes());
+    message.extend_from_slice(&0_i32.to_le_bytes());
+    message.extend_from_slice(&(OpCode::Msg as i32).to_le_bytes());
+    message.extend_from_slice(&flags.to_le_bytes());
+    message.push(0);
+    message.extend_from_slice(command.as_bytes());
+    stream.write_all(&message).await.unwrap();
+}
+
+// Reads an OP_MSG reply made of a single document section
+#[allow(dead_code)]
+pub async fn receive_op_msg(stream: &mut SslStream<TcpStream>) -> (Header, u32, RawDocumentBuf) {
+    let header = reader::read_header(stream).await.unwrap().unwrap();
+    assert_eq!(header.op_code, OpCode::Msg);
+
+    let mut body = vec![0; header.length as usize - Header::LENGTH];
+    stream.read_exact(&mut body).await.unwrap();
+    let flags = u32::from_le_bytes(body[0..4].try_into().unwrap());
+    assert_eq!(body[4], 0);
+    let length = i32::from_le_bytes(body[5..9].try_into().unwrap()) as usize;
+    let document = RawDocumentBuf::from_bytes(body[5..5 + length].to_vec()).unwrap();
+    (header, flags, document)
+}
+
+// Sends a command and returns its successful reply
+#[allow(dead_code)]
+pub async fn run_raw_command(
+    stream: &mut SslStream<TcpStream>,
+    request_id: i32,
+    command: &RawDocument,
+) -> RawDocumentBuf {
+    send_op_msg(stream, request_id, 0, command).await;
+    let (header, flags, response) = receive_op_msg(stream).await;
+    assert_eq!(header.response_to, request_id);
+    assert_eq!(flags & MORE_TO_COME, 0);
+    let ok = response.get("ok").unwrap().unwrap();
+    assert!(
+        ok.as_f64() == Some(1.0) || ok.as_i32() == Some(1),
+        "{:?}",
+        response
+    );
+    response
+}
@@ -0,0 +1,122 @@
+use documentdb_gateway::{
+    error::ErrorCode,
+    protocol::{
+        compression::{Compressor, COMPRESSED_PREAMBLE_LENGTH},
+        opcode::OpCode,
+        reader, MAX_MESSAGE_SIZE_BYTES,
+    },
+};
+
+// A payload that compresses well, similar to a batch of small documents
+fn payload() -> Vec<u8> {
+    (0..4096)
+        .flat_map(|i: u32| (i % 64).to_le_bytes())
+        .collect()
+}
+
+// Builds an OP_COMPRESSED body: the preamble followed by the compressed message
+fn compressed_message(compressor_id: u8, uncompressed_size: i32, compressed: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(COMPRESSED_PREAMBLE_LENGTH + compressed.len());
+    message.extend_from_slice(&(OpCode::Msg as i32).to_le_bytes());
+    message.extend_from_slice(&uncompressed_size.to_le_bytes());
+    message.push(compressor_id);
+    message.extend_from_slice(compressed);
+    message
+}
+
+fn assert_bad_value<T: std::fmt::Debug>(result: documentdb_gateway::error::Result<T>) {
+    assert!(matches!(
+        result.unwrap_err().error_code_enum(),
+        Some(ErrorCode::BadValue)
+    ));
+}
+
+#[test]
+fn round_trips_every_supported_compressor() {
+    let payload = payload();
+    for compressor in Compressor::SUPPORTED {
+        let compressed = compressor.compress(&payload).unwrap();
+        assert!(compressed.len() < payload.len(), "{:?}", compressor);
+        assert_eq!(
+            compressor.decompress(&compressed, payload.len()).unwrap(),
+            payload,
+            "{:?}",
+            compressor
+        );
+    }
+}
+
+#[test]
+fn unwraps_negotiated_op_compressed_message() {
+    let payload = payload();
+    for compressor in Compressor::SUPPORTED {
+        let message = compressed_message(
+            compressor.id(),
+            payload.len() as i32,
+            &compressor.compress(&payload).unwrap(),
+        );
+
+        let (op_code, used, decompressed) = reader::decompress(&message, &[compressor]).unwrap();
+        assert_eq!(op_code, OpCode::Msg);
+        assert_eq!(used, compressor);
+        assert_eq!(decompressed, payload);
+    }
+}
+
+#[test]
+fn rejects_mismatched_uncompressed_size() {
+    let payload = payload();
+    for compressor in Compressor::SUPPORTED {
+        let compressed = compressor.compress(&payload).unwrap();
+        assert_bad_value(compressor.decompress(&compressed, payload.len() - 1));
+        assert_bad_value(compressor.decompress(&compressed, payload.len() + 1));
+    }
+}
+
+#[test]
+fn rejects_uncompressed_size_above_the_message_limit() {
+    let payload = payload();
+    let message = compressed_message(
+        Compressor::Zlib.id(),
+        MAX_MESSAGE_SIZE_BYTES + 1,
+        &Compressor::Zlib.compress(&payload).unwrap(),
+    );
+    assert_bad_value(reader::decompress(&message, &[Compressor::Zlib]));
+
+    let message = compressed_message(Compressor::Zlib.id(), -1, &[]);
+    assert_bad_value(reader::decompress(&message, &[Compressor::Zlib]));
+}
+
+#[test]
+fn rejects_unknown_and_unnegotiated_compressors() {
+    assert_eq!(Compressor::from_id(4), None);
+    assert_eq!(Compressor::from_name("lz4"), None);
+
+    let payload = payload();
+    let message = compressed_message(4, payload.len() as i32, &payload);
+    assert_bad_value(reader::decompress(&message, &Compressor::SUPPORTED));
+
+    let message = compressed_message(
+        Compressor::Zstd.id(),
+        payload.len() as i32,
+        &Compressor::Zstd.compress(&payload).unwrap(),
+    );
+    assert_bad_value(reader::decompress(&message, &[Compressor::Snappy]));
+}
+
+#[test]
+fn accepts_noop_without_negotiation() {
+    let payload = payload();
+    let message = compressed_message(Compressor::Noop.id(), payload.len() as i32, &payload);
+    let (_, compressor, decompressed) = reader::decompress(&message, &[]).unwrap();
+    assert_eq!(compressor, Compressor::Noop);
+    assert_eq!(decompressed, payload);
+}
+
+#[test]
+fn negotiates_by_name() {
+    for compressor in Compressor::SUPPORTED {
+        assert_eq!(Compressor::from_name(compressor.name()), Some(compressor));
+        assert_eq!(Compressor::from_id(compressor.id()), Some(compressor));
+    }
+}
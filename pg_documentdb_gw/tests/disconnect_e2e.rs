@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use bson::{doc, rawdoc};
+
+mod common;
+
+async fn finds_waiting_on_lock(monitor: &tokio_postgres::Client) -> i64 {
+    monitor
+        .query_one(
+            "SELECT COUNT(*) FROM pg_stat_activity WHERE wait_event_type = 'Lock' AND query LIKE '%find_cursor_first_page%' AND pid <> pg_backend_pid()",
+            &[],
+        )
+        .await
+        .unwrap()
+        .get(0)
+}
+
+// Polls the backend until the expected number of finds is waiting on the lock
+async fn wait_for_finds_waiting_on_lock(monitor: &tokio_postgres::Client, expected: i64) -> bool {
+    for _ in 0..100 {
+        if finds_waiting_on_lock(monitor).await == expected {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn disconnect_cancels_backend_query() {
+    let client = common::initialize().await;
+    let db = common::setup_db(&client, "disconnect").await;
+    db.collection("test")
+        .insert_one(doc! {"_id": 1})
+        .await
+        .unwrap();
+
+    // Hold a lock on the collection's table, so that a find waits in the backend
+    let monitor = common::postgres_client().await;
+    let collection_id: i64 = monitor
+        .query_one(
+            "SELECT collection_id FROM documentdb_api_catalog.collections WHERE database_name = 'disconnect' AND collection_name = 'test'",
+            &[],
+        )
+        .await
+        .unwrap()
+        .get(0);
+    let mut locker = common::postgres_client().await;
+    let lock = locker.transaction().await.unwrap();
+    lock.batch_execute(&format!(
+        "LOCK TABLE documentdb_data.documents_{} IN ACCESS EXCLUSIVE MODE",
+        collection_id
+    ))
+    .await
+    .unwrap();
+
+    let mut stream = common::connect_raw().await;
+    common::send_op_msg(
+        &mut stream,
+        2,
+        0,
+        &rawdoc! { "find": "test", "$db": "disconnect" },
+    )
+    .await;
+    assert!(wait_for_finds_waiting_on_lock(&monitor, 1).await);
+
+    // Closing the connection cancels the query rather than leaving it waiting for the lock
+    drop(stream);
+    assert!(wait_for_finds_waiting_on_lock(&monitor, 0).await);
+
+    lock.rollback().await.unwrap();
+}
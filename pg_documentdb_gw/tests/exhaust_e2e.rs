@@ -0,0 +1,61 @@
+use bson::{doc, rawdoc};
+
+mod common;
+
+use common::{receive_op_msg, run_raw_command, send_op_msg, MORE_TO_COME};
+
+const EXHAUST_ALLOWED: u32 = 1 << 16;
+
+#[tokio::test]
+async fn get_more_streams_batches_when_exhaust_allowed() {
+    let client = common::initialize().await;
+    let db = common::setup_db(&client, "exhaust").await;
+    db.collection("test")
+        .insert_many((0..5).map(|i| doc! {"_id": i}))
+        .await
+        .unwrap();
+
+    let mut stream = common::connect_raw().await;
+
+    let find = run_raw_command(
+        &mut stream,
+        2,
+        &rawdoc! { "find": "test", "batchSize": 1, "$db": "exhaust" },
+    )
+    .await;
+    let cursor = find.get_document("cursor").unwrap();
+    let cursor_id = cursor.get_i64("id").unwrap();
+    assert_ne!(cursor_id, 0);
+    let mut returned = cursor.get_array("firstBatch").unwrap().into_iter().count();
+
+    send_op_msg(
+        &mut stream,
+        3,
+        EXHAUST_ALLOWED,
+        &rawdoc! { "getMore": cursor_id, "collection": "test", "batchSize": 1, "$db": "exhaust" },
+    )
+    .await;
+
+    // Each reply answers the previous one, and all but the last have moreToCome set
+    let mut response_to = 3;
+    let mut replies = 0;
+    loop {
+        let (header, flags, response) = receive_op_msg(&mut stream).await;
+        assert_eq!(header.response_to, response_to);
+        response_to = header.request_id;
+        replies += 1;
+
+        let cursor = response.get_document("cursor").unwrap();
+        returned += cursor.get_array("nextBatch").unwrap().into_iter().count();
+        if cursor.get_i64("id").unwrap() == 0 {
+            assert_eq!(flags & MORE_TO_COME, 0);
+            break;
+        }
+        assert_eq!(flags & MORE_TO_COME, MORE_TO_COME);
+    }
+    assert!(replies > 1);
+    assert_eq!(returned, 5);
+
+    // The stream ended, the next request gets its own reply
+    run_raw_command(&mut stream, 4, &rawdoc! { "ping": 1, "$db": "admin" }).await;
+}
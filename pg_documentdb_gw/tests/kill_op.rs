@@ -0,0 +1,31 @@
+use documentdb_gateway::processor::parse_op_id;
+
+#[test]
+fn parses_local_op_id() {
+    assert_eq!(
+        parse_op_id("10000012345:1718000000123456").unwrap(),
+        (12345, "1718000000123456")
+    );
+}
+
+#[test]
+fn rejects_op_id_of_other_node() {
+    // The same backend pid on node 2 is a different backend
+    assert!(parse_op_id("20000012345:1718000000123456").is_err());
+    assert!(parse_op_id("12345:1718000000123456").is_err());
+}
+
+#[test]
+fn rejects_malformed_op_ids() {
+    for op in [
+        "",
+        "10000012345",
+        "10000012345:",
+        "10000012345:17180000abc",
+        "pid:1718000000123456",
+        "-10000012345:1718000000123456",
+        "19999999999:1718000000123456",
+    ] {
+        assert!(parse_op_id(op).is_err(), "{}", op);
+    }
+}
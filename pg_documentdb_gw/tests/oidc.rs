@@ -0,0 +1,210 @@
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use documentdb_gateway::{
+    configuration::OidcOptions,
+    oidc::{self, JwksCache},
+};
+use jsonwebtoken::{jwk::JwkSet, Algorithm, EncodingKey, Header};
+use openssl::{pkey::Private, rsa::Rsa};
+use serde_json::json;
+
+const ISSUER: &str = "https://issuer.example.com";
+const AUDIENCE: &str = "documentdb";
+
+struct KeySet {
+    key: Rsa<Private>,
+    jwks: serde_json::Value,
+    path: PathBuf,
+}
+
+impl KeySet {
+    // Writes a JWKS file holding the public half of a new RSA key
+    fn new(name: &str) -> Self {
+        let key = Rsa::generate(2048).unwrap();
+        let jwks = json!({
+            "keys": [{
+                "kty": "RSA",
+                "kid": "key-1",
+                "alg": "RS256",
+                "use": "sig",
+                "n": URL_SAFE_NO_PAD.encode(key.n().to_vec()),
+                "e": URL_SAFE_NO_PAD.encode(key.e().to_vec()),
+            }]
+        });
+        let path = std::env::temp_dir().join(format!(
+            "documentdb-oidc-{}-{}.json",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, jwks.to_string()).unwrap();
+        KeySet { key, jwks, path }
+    }
+
+    fn key_set(&self) -> JwkSet {
+        serde_json::from_value(self.jwks.clone()).unwrap()
+    }
+
+    fn validate(&self, options: &OidcOptions, token: &str) -> bool {
+        oidc::validate_token(options, &self.key_set(), token).is_ok()
+    }
+
+    fn options(&self) -> OidcOptions {
+        OidcOptions {
+            jwks_file_path: self.path.to_string_lossy().to_string(),
+            issuer: ISSUER.to_string(),
+            audience: AUDIENCE.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn sign(&self, claims: &serde_json::Value) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some("key-1".to_string());
+        jsonwebtoken::encode(
+            &header,
+            claims,
+            &EncodingKey::from_rsa_pem(&self.key.private_key_to_pem().unwrap()).unwrap(),
+        )
+        .unwrap()
+    }
+}
+
+impl Drop for KeySet {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn claims() -> serde_json::Value {
+    json!({
+        "iss": ISSUER,
+        "aud": AUDIENCE,
+        "sub": "app_user",
+        "role": "mapped_role",
+        "exp": now() + 3600,
+    })
+}
+
+#[test]
+fn validates_token() {
+    let keys = KeySet::new("valid");
+    let identity =
+        oidc::validate_token(&keys.options(), &keys.key_set(), &keys.sign(&claims())).unwrap();
+    assert_eq!(identity.username, "app_user");
+    assert_eq!(
+        identity.expires_at,
+        UNIX_EPOCH + Duration::from_secs(claims()["exp"].as_u64().unwrap())
+    );
+}
+
+#[test]
+fn maps_principal_claim() {
+    let keys = KeySet::new("claim");
+    let options = OidcOptions {
+        principal_claim: Some("role".to_string()),
+        ..keys.options()
+    };
+    let identity = oidc::validate_token(&options, &keys.key_set(), &keys.sign(&claims())).unwrap();
+    assert_eq!(identity.username, "mapped_role");
+
+    let options = OidcOptions {
+        principal_claim: Some("missing".to_string()),
+        ..keys.options()
+    };
+    assert!(!keys.validate(&options, &keys.sign(&claims())));
+}
+
+#[test]
+fn rejects_wrong_issuer_and_audience() {
+    let keys = KeySet::new("issuer");
+
+    let mut token = claims();
+    token["iss"] = json!("https://other.example.com");
+    assert!(!keys.validate(&keys.options(), &keys.sign(&token)));
+
+    let mut token = claims();
+    token["aud"] = json!("other");
+    assert!(!keys.validate(&keys.options(), &keys.sign(&token)));
+}
+
+#[test]
+fn rejects_expired_token() {
+    let keys = KeySet::new("expired");
+    let mut token = claims();
+    token["exp"] = json!(now() - 3600);
+    assert!(!keys.validate(&keys.options(), &keys.sign(&token)));
+}
+
+#[test]
+fn rejects_token_from_other_key() {
+    let keys = KeySet::new("trusted");
+    let other = KeySet::new("untrusted");
+    assert!(!keys.validate(&keys.options(), &other.sign(&claims())));
+}
+
+#[test]
+fn rejects_symmetric_token() {
+    let keys = KeySet::new("symmetric");
+    let token = jsonwebtoken::encode(
+        &Header::new(Algorithm::HS256),
+        &claims(),
+        &EncodingKey::from_secret(b"secret"),
+    )
+    .unwrap();
+    assert!(!keys.validate(&keys.options(), &token));
+}
+
+#[tokio::test]
+async fn caches_key_set() {
+    let keys = KeySet::new("cached");
+    let cache = JwksCache::new();
+    let first = cache.get(&keys.options()).await.unwrap();
+
+    // Within the refresh interval the file is not read again
+    std::fs::remove_file(&keys.path).unwrap();
+    let second = cache.get(&keys.options()).await.unwrap();
+    assert!(Arc::ptr_eq(&first, &second));
+}
+
+#[tokio::test]
+async fn refreshes_key_set_after_interval() {
+    let keys = KeySet::new("rotated");
+    let options = OidcOptions {
+        jwks_refresh_interval_secs: Some(0),
+        ..keys.options()
+    };
+    let cache = JwksCache::new();
+    let token = keys.sign(&claims());
+    assert!(oidc::validate_token(&options, &cache.get(&options).await.unwrap(), &token).is_ok());
+
+    // The rotated key replaces the old one
+    let rotated = KeySet::new("rotated-next");
+    std::fs::rename(&rotated.path, &keys.path).unwrap();
+    let key_set = cache.get(&options).await.unwrap();
+    assert!(oidc::validate_token(&options, &key_set, &token).is_err());
+    assert!(oidc::validate_token(&options, &key_set, &rotated.sign(&claims())).is_ok());
+
+    // A failed refresh keeps the previous keys
+    std::fs::write(&keys.path, "not json").unwrap();
+    let key_set = cache.get(&options).await.unwrap();
+    assert!(oidc::validate_token(&options, &key_set, &rotated.sign(&claims())).is_ok());
+}
+
+#[tokio::test]
+async fn fails_without_key_set() {
+    let keys = KeySet::new("missing");
+    std::fs::remove_file(&keys.path).unwrap();
+    assert!(JwksCache::new().get(&keys.options()).await.is_err());
+}
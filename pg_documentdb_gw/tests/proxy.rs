@@ -0,0 +1,198 @@
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use documentdb_gateway::protocol::proxy::{read_proxy_header, resolve_client_address};
+use ipnet::IpNet;
+use tokio::io::AsyncWriteExt;
+
+const V2_SIGNATURE: [u8; 12] = [
+    0x0D, 0x0A, 0x0D, 0x0A, 0x00, 0x0D, 0x0A, 0x51, 0x55, 0x49, 0x54, 0x0A,
+];
+const TIMEOUT: Duration = Duration::from_millis(50);
+
+fn v2_header(version_command: u8, family_protocol: u8, addresses: &[u8]) -> Vec<u8> {
+    let mut header = V2_SIGNATURE.to_vec();
+    header.push(version_command);
+    header.push(family_protocol);
+    header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+    header.extend_from_slice(addresses);
+    header
+}
+
+fn v2_inet_addresses() -> Vec<u8> {
+    let mut addresses = vec![192, 0, 2, 7, 10, 0, 0, 1];
+    addresses.extend_from_slice(&5000_u16.to_be_bytes());
+    addresses.extend_from_slice(&10260_u16.to_be_bytes());
+    addresses
+}
+
+fn proxy() -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::new(10, 0, 0, 2), 40000))
+}
+
+fn trusted() -> Vec<IpNet> {
+    vec!["10.0.0.0/24".parse().unwrap()]
+}
+
+#[tokio::test]
+async fn reads_v1_tcp4_header() {
+    let mut stream: &[u8] = b"PROXY TCP4 192.0.2.7 10.0.0.1 5000 10260\r\nrest";
+    assert_eq!(
+        read_proxy_header(&mut stream).await.unwrap(),
+        Some(SocketAddr::from((Ipv4Addr::new(192, 0, 2, 7), 5000)))
+    );
+    // Nothing past the header is consumed
+    assert_eq!(stream, b"rest");
+}
+
+#[tokio::test]
+async fn reads_v1_tcp6_header() {
+    let mut stream: &[u8] = b"PROXY TCP6 2001:db8::7 2001:db8::1 5000 10260\r\n";
+    assert_eq!(
+        read_proxy_header(&mut stream).await.unwrap(),
+        Some(SocketAddr::from((
+            "2001:db8::7".parse::<Ipv6Addr>().unwrap(),
+            5000
+        )))
+    );
+}
+
+#[tokio::test]
+async fn reads_v1_unknown_as_local() {
+    let mut stream: &[u8] = b"PROXY UNKNOWN\r\n";
+    assert_eq!(read_proxy_header(&mut stream).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn rejects_malformed_v1_headers() {
+    let headers: [&[u8]; 3] = [
+        b"PROXY TCP4 2001:db8::7 10.0.0.1 5000 10260\r\n",
+        b"PROXY TCP4 192.0.2.7 10.0.0.1 70000 10260\r\n",
+        b"PROXY TCP4 192.0.2.7 10.0.0.1\r\n",
+    ];
+    for header in headers {
+        let mut stream = header;
+        assert!(read_proxy_header(&mut stream).await.is_err());
+    }
+
+    let mut long = b"PROXY TCP4 ".to_vec();
+    long.extend_from_slice(&[b'1'; 200]);
+    assert!(read_proxy_header(&mut long.as_slice()).await.is_err());
+}
+
+#[tokio::test]
+async fn reads_v2_inet_header() {
+    let mut header = v2_header(0x21, 0x11, &v2_inet_addresses());
+    header.extend_from_slice(b"rest");
+    let mut stream = header.as_slice();
+    assert_eq!(
+        read_proxy_header(&mut stream).await.unwrap(),
+        Some(SocketAddr::from((Ipv4Addr::new(192, 0, 2, 7), 5000)))
+    );
+    assert_eq!(stream, b"rest");
+}
+
+#[tokio::test]
+async fn reads_v2_inet6_header() {
+    let source: Ipv6Addr = "2001:db8::7".parse().unwrap();
+    let mut addresses = source.octets().to_vec();
+    addresses.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+    addresses.extend_from_slice(&5000_u16.to_be_bytes());
+    addresses.extend_from_slice(&10260_u16.to_be_bytes());
+
+    let header = v2_header(0x21, 0x21, &addresses);
+    assert_eq!(
+        read_proxy_header(&mut header.as_slice()).await.unwrap(),
+        Some(SocketAddr::from((source, 5000)))
+    );
+}
+
+#[tokio::test]
+async fn reads_v2_local_command() {
+    let header = v2_header(0x20, 0x00, &[]);
+    assert_eq!(
+        read_proxy_header(&mut header.as_slice()).await.unwrap(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn rejects_invalid_v2_headers() {
+    // Unsupported version and command
+    let header = v2_header(0x11, 0x11, &v2_inet_addresses());
+    assert!(read_proxy_header(&mut header.as_slice()).await.is_err());
+    let header = v2_header(0x22, 0x11, &v2_inet_addresses());
+    assert!(read_proxy_header(&mut header.as_slice()).await.is_err());
+
+    // Addresses too short for the family
+    let header = v2_header(0x21, 0x11, &[192, 0, 2, 7]);
+    assert!(read_proxy_header(&mut header.as_slice()).await.is_err());
+}
+
+#[tokio::test]
+async fn rejects_truncated_headers() {
+    let mut stream: &[u8] = b"PROXY TCP4 192.0.2.7";
+    assert!(read_proxy_header(&mut stream).await.is_err());
+
+    let header = v2_header(0x21, 0x11, &v2_inet_addresses());
+    for length in [6, V2_SIGNATURE.len() + 2, header.len() - 1] {
+        assert!(read_proxy_header(&mut &header[..length]).await.is_err());
+    }
+}
+
+#[tokio::test]
+async fn rejects_missing_header() {
+    let mut stream: &[u8] = b"not a proxy header";
+    assert!(read_proxy_header(&mut stream).await.is_err());
+}
+
+#[tokio::test]
+async fn resolves_address_from_trusted_proxy() {
+    let header = v2_header(0x21, 0x11, &v2_inet_addresses());
+    assert_eq!(
+        resolve_client_address(&mut header.as_slice(), proxy(), &trusted(), TIMEOUT)
+            .await
+            .unwrap(),
+        SocketAddr::from((Ipv4Addr::new(192, 0, 2, 7), 5000))
+    );
+
+    // A LOCAL connection from the proxy keeps the proxy's address
+    let header = v2_header(0x20, 0x00, &[]);
+    assert_eq!(
+        resolve_client_address(&mut header.as_slice(), proxy(), &trusted(), TIMEOUT)
+            .await
+            .unwrap(),
+        proxy()
+    );
+}
+
+#[tokio::test]
+async fn ignores_header_from_untrusted_source() {
+    let peer = SocketAddr::from((Ipv4Addr::new(10, 0, 1, 2), 40000));
+    let header = v2_header(0x21, 0x11, &v2_inet_addresses());
+    let mut stream = header.as_slice();
+    assert_eq!(
+        resolve_client_address(&mut stream, peer, &trusted(), TIMEOUT)
+            .await
+            .unwrap(),
+        peer
+    );
+    // Nothing is read from the stream of an untrusted peer
+    assert_eq!(stream, header.as_slice());
+}
+
+#[tokio::test]
+async fn times_out_waiting_for_header() {
+    // The client stays connected but never completes the header
+    let (mut client, mut server) = tokio::io::duplex(64);
+    client.write_all(b"PROXY TCP4").await.unwrap();
+
+    assert!(
+        resolve_client_address(&mut server, proxy(), &trusted(), TIMEOUT)
+            .await
+            .is_err()
+    );
+    drop(client);
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use bson::rawdoc;
+use documentdb_gateway::{
+    context::{RetryableWrite, RetryableWriteKey, RetryableWriteStore},
+    error::ErrorCode,
+};
+
+const SESSION: &[u8] = b"session-1";
+
+fn key(transaction_number: i64, statement_id: i32) -> RetryableWriteKey {
+    RetryableWriteKey {
+        session_id: SESSION.to_vec(),
+        transaction_number,
+        statement_id,
+    }
+}
+
+async fn execute(store: &RetryableWriteStore, key: RetryableWriteKey) {
+    let RetryableWrite::Execute(guard) = store.begin(key).await.unwrap() else {
+        panic!("Expected the statement to execute");
+    };
+    guard.complete(Some(rawdoc! { "ok": 1.0, "n": 1 })).await;
+}
+
+#[tokio::test]
+async fn replays_completed_statement() {
+    let store = RetryableWriteStore::new();
+    execute(&store, key(1, 0)).await;
+
+    let RetryableWrite::Replay(response) = store.begin(key(1, 0)).await.unwrap() else {
+        panic!("Expected the statement to be replayed");
+    };
+    assert_eq!(response, rawdoc! { "ok": 1.0, "n": 1 });
+
+    // Another statement of the same write still runs
+    execute(&store, key(1, 1)).await;
+}
+
+#[tokio::test]
+async fn failed_statement_runs_again() {
+    let store = Arc::new(RetryableWriteStore::new());
+    let RetryableWrite::Execute(guard) = store.begin(key(1, 0)).await.unwrap() else {
+        panic!("Expected the statement to execute");
+    };
+
+    // The retry waits for the running statement
+    let retry = tokio::spawn({
+        let store = store.clone();
+        async move { store.begin(key(1, 0)).await.unwrap() }
+    });
+    tokio::task::yield_now().await;
+    assert!(!retry.is_finished());
+
+    guard.complete(None).await;
+    let RetryableWrite::Execute(guard) = retry.await.unwrap() else {
+        panic!("Expected the statement to execute again");
+    };
+    guard.complete(Some(rawdoc! { "ok": 1.0, "n": 1 })).await;
+}
+
+#[tokio::test]
+async fn retry_waits_for_running_statement() {
+    let store = Arc::new(RetryableWriteStore::new());
+    let RetryableWrite::Execute(guard) = store.begin(key(1, 0)).await.unwrap() else {
+        panic!("Expected the statement to execute");
+    };
+
+    let retry = tokio::spawn({
+        let store = store.clone();
+        async move { store.begin(key(1, 0)).await.unwrap() }
+    });
+    tokio::task::yield_now().await;
+    assert!(!retry.is_finished());
+
+    guard.complete(Some(rawdoc! { "ok": 1.0, "n": 1 })).await;
+    let RetryableWrite::Replay(response) = retry.await.unwrap() else {
+        panic!("Expected the statement to be replayed");
+    };
+    assert_eq!(response, rawdoc! { "ok": 1.0, "n": 1 });
+}
+
+#[tokio::test]
+async fn rejects_older_transaction_number() {
+    let store = RetryableWriteStore::new();
+    execute(&store, key(1, 0)).await;
+    execute(&store, key(2, 0)).await;
+
+    let error = store.begin(key(1, 0)).await.err().unwrap();
+    assert!(matches!(
+        error.error_code_enum(),
+        Some(ErrorCode::TransactionTooOld)
+    ));
+
+    store.remove_session(SESSION).await;
+    execute(&store, key(1, 0)).await;
+}
+
+#[tokio::test]
+async fn abandoned_statement_stays_in_progress() {
+    let store = RetryableWriteStore::new();
+    let RetryableWrite::Execute(guard) = store.begin(key(1, 0)).await.unwrap() else {
+        panic!("Expected the statement to execute");
+    };
+    drop(guard);
+
+    // The write may have been applied, so a retry must not run it again
+    let error = store.begin(key(1, 0)).await.err().unwrap();
+    assert!(matches!(
+        error.error_code_enum(),
+        Some(ErrorCode::ConflictingOperationInProgress)
+    ));
+
+    // A newer write on the session moves past it
+    execute(&store, key(2, 0)).await;
+}
@@ -0,0 +1,49 @@
+use bson::{doc, Document};
+
+mod common;
+
+#[tokio::test]
+async fn retried_insert_is_replayed() {
+    let client = common::initialize().await;
+    let db = common::setup_db(&client, "retryable_writes").await;
+
+    let session = db.run_command(doc! {"startSession": 1}).await.unwrap();
+    let lsid = session.get_document("id").unwrap().clone();
+    let insert = doc! {
+        "insert": "test",
+        "documents": [{"_id": 1, "a": 1}, {"_id": 2, "a": 2}],
+        "lsid": lsid.clone(),
+        "txnNumber": 1_i64,
+        "stmtId": 0,
+    };
+
+    let first = db.run_command(insert.clone()).await.unwrap();
+    assert_eq!(first.get_i32("n").unwrap(), 2);
+
+    // The retry gets the original response instead of failing on the duplicate keys
+    let retry = db.run_command(insert).await.unwrap();
+    assert_eq!(retry.get_i32("n").unwrap(), 2);
+    assert!(retry.get_array("writeErrors").is_err());
+    assert_eq!(
+        db.collection::<Document>("test")
+            .count_documents(doc! {})
+            .await
+            .unwrap(),
+        2
+    );
+
+    // A new transaction number runs the write again
+    let next = db
+        .run_command(doc! {
+            "insert": "test",
+            "documents": [{"_id": 3, "a": 3}],
+            "lsid": lsid.clone(),
+            "txnNumber": 2_i64,
+            "stmtId": 0,
+        })
+        .await
+        .unwrap();
+    assert_eq!(next.get_i32("n").unwrap(), 1);
+
+    db.run_command(doc! {"endSessions": [lsid]}).await.unwrap();
+}
@@ -0,0 +1,55 @@
+use documentdb_gateway::context::ScramKeys;
+
+// The SCRAM-SHA-256 exchange from RFC 7677 section 3, for user "user" with password "pencil"
+const SECRET: &str = "SCRAM-SHA-256$4096:W22ZaJ0SNY7soEsUEjb6gQ==$WG5d8oPm3OtcPnkdi4Uo7BkeZkBFzpcXkuLmtbsT4qY=:wfPLwcE6nTWhTAmQ7tl2KeoiWGPlZqQxSrmfPwDl2dU=";
+const AUTH_MESSAGE: &str = "n=user,r=rOprNGfwEbeRWgbNEkqO,\
+    r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096,\
+    c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0";
+const CLIENT_PROOF: &str = "dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ=";
+const SERVER_SIGNATURE: &str = "6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=";
+
+#[test]
+fn parses_secret() {
+    let keys = ScramKeys::parse(SECRET).unwrap();
+    assert_eq!(keys.salt, "W22ZaJ0SNY7soEsUEjb6gQ==");
+    assert_eq!(keys.iterations, 4096);
+}
+
+#[test]
+fn rejects_other_secrets() {
+    for secret in [
+        "md5a3556571e93b0d20722ba62be61e8c2d",
+        "SCRAM-SHA-256$4096:W22ZaJ0SNY7soEsUEjb6gQ==",
+        "SCRAM-SHA-256$many:W22ZaJ0SNY7soEsUEjb6gQ==$AAAA:AAAA",
+        "",
+    ] {
+        assert!(ScramKeys::parse(secret).is_none(), "{}", secret);
+    }
+}
+
+#[test]
+fn verifies_rfc_7677_proof() {
+    let keys = ScramKeys::parse(SECRET).unwrap();
+    assert_eq!(
+        keys.verify_proof(AUTH_MESSAGE, CLIENT_PROOF).unwrap(),
+        Some(SERVER_SIGNATURE.to_string())
+    );
+}
+
+#[test]
+fn rejects_wrong_proof() {
+    let keys = ScramKeys::parse(SECRET).unwrap();
+
+    let mut proof = CLIENT_PROOF.to_string();
+    proof.replace_range(0..1, "e");
+    for proof in [proof.as_str(), "dHzbZapW", "not base64!", ""] {
+        assert_eq!(keys.verify_proof(AUTH_MESSAGE, proof).unwrap(), None);
+    }
+
+    // The proof is bound to the auth message
+    let auth_message = AUTH_MESSAGE.replace("n=user", "n=other");
+    assert_eq!(
+        keys.verify_proof(&auth_message, CLIENT_PROOF).unwrap(),
+        None
+    );
+}
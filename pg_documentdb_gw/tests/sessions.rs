@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use documentdb_gateway::{context::SessionStore, error::ErrorCode};
+
+const SESSION: &[u8] = b"session-1";
+
+#[tokio::test]
+async fn session_is_bound_to_its_owner() {
+    let store = SessionStore::new(Duration::from_secs(60));
+    store.touch(SESSION, "owner").await.unwrap();
+    store.touch(SESSION, "owner").await.unwrap();
+
+    let error = store.touch(SESSION, "other").await.unwrap_err();
+    assert!(matches!(
+        error.error_code_enum(),
+        Some(ErrorCode::Unauthorized)
+    ));
+
+    let error = store.check_owner(SESSION, "other").await.unwrap_err();
+    assert!(matches!(
+        error.error_code_enum(),
+        Some(ErrorCode::Unauthorized)
+    ));
+    store.check_owner(SESSION, "owner").await.unwrap();
+}
+
+#[tokio::test]
+async fn unknown_session_has_no_owner() {
+    let store = SessionStore::new(Duration::from_secs(60));
+    store.check_owner(SESSION, "anyone").await.unwrap();
+}
+
+#[tokio::test]
+async fn lists_sessions_by_owner() {
+    let store = SessionStore::new(Duration::from_secs(60));
+    store.touch(b"a", "owner").await.unwrap();
+    store.touch(b"b", "other").await.unwrap();
+
+    assert_eq!(store.session_ids(&["owner"]).await, vec![b"a".to_vec()]);
+    assert_eq!(store.session_ids(&[]).await.len(), 2);
+}
+
+#[tokio::test]
+async fn expires_idle_sessions() {
+    let store = SessionStore::new(Duration::from_millis(10));
+    store.touch(SESSION, "owner").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    assert_eq!(store.remove_expired().await, vec![SESSION.to_vec()]);
+    assert!(store.remove(SESSION).await.is_none());
+
+    // An expired session can be started again by another user
+    store.touch(SESSION, "other").await.unwrap();
+}
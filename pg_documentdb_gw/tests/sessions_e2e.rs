@@ -0,0 +1,98 @@
+use bson::{doc, Document};
+use documentdb_gateway::postgres::create_query_catalog;
+use mongodb::{error::ErrorKind, Client};
+
+mod common;
+
+const UNAUTHORIZED: i32 = 13;
+
+async fn other_user_client() -> Client {
+    common::create_user("session_other", "session_other", &create_query_catalog())
+        .await
+        .unwrap();
+    common::get_client_with_credentials("session_other", "session_other")
+}
+
+fn assert_unauthorized(result: mongodb::error::Result<Document>) {
+    match *result.unwrap_err().kind {
+        ErrorKind::Command(e) => assert_eq!(e.code, UNAUTHORIZED),
+        e => panic!("Expected Unauthorized, got {:?}", e),
+    }
+}
+
+#[tokio::test]
+async fn session_of_another_user_is_unauthorized() {
+    let client = common::initialize().await;
+    let db = common::setup_db(&client, "session_owner").await;
+
+    let session = db.run_command(doc! {"startSession": 1}).await.unwrap();
+    let lsid = session.get_document("id").unwrap().clone();
+    db.run_command(doc! {"refreshSessions": [lsid.clone()]})
+        .await
+        .unwrap();
+
+    let other = other_user_client().await.database("session_owner");
+    assert_unauthorized(
+        other
+            .run_command(doc! {"find": "test", "lsid": lsid.clone()})
+            .await,
+    );
+    assert_unauthorized(
+        other
+            .run_command(doc! {"refreshSessions": [lsid.clone()]})
+            .await,
+    );
+    assert_unauthorized(
+        other
+            .run_command(doc! {"endSessions": [lsid.clone()]})
+            .await,
+    );
+    assert_unauthorized(
+        other
+            .run_command(doc! {"killSessions": [lsid.clone()]})
+            .await,
+    );
+
+    db.run_command(doc! {"endSessions": [lsid]}).await.unwrap();
+}
+
+#[tokio::test]
+async fn transaction_of_another_user_is_unauthorized() {
+    let client = common::initialize().await;
+    let db = common::setup_db(&client, "transaction_owner").await;
+    db.collection::<Document>("test")
+        .insert_one(doc! {"a": 1})
+        .await
+        .unwrap();
+
+    let mut session = client.start_session().await.unwrap();
+    session.start_transaction().await.unwrap();
+    db.collection::<Document>("test")
+        .insert_one(doc! {"a": 2})
+        .session(&mut session)
+        .await
+        .unwrap();
+
+    // Another user presenting the session id and transaction number must not join the transaction
+    let other = other_user_client().await.database("transaction_owner");
+    assert_unauthorized(
+        other
+            .run_command(doc! {
+                "insert": "test",
+                "documents": [{"a": 3}],
+                "lsid": session.id().clone(),
+                "txnNumber": 1_i64,
+                "autocommit": false,
+            })
+            .await,
+    );
+
+    session.abort_transaction().await.unwrap();
+    assert_eq!(
+        db.collection::<Document>("test")
+            .count_documents(doc! {})
+            .await
+            .unwrap(),
+        1
+    );
+}
@@ -0,0 +1,74 @@
+use documentdb_gateway::{
+    configuration::{CertificateOptions, ClientCertificateMode},
+    tls::parse_tls_version,
+};
+use openssl::ssl::SslVersion;
+
+#[test]
+fn parses_tls_versions() {
+    for (version, expected) in [
+        ("TLS1", SslVersion::TLS1),
+        ("TLS1.0", SslVersion::TLS1),
+        ("TLSv1.1", SslVersion::TLS1_1),
+        ("tls1.2", SslVersion::TLS1_2),
+        ("TLS1_2", SslVersion::TLS1_2),
+        ("TLSv1.3", SslVersion::TLS1_3),
+    ] {
+        assert!(
+            parse_tls_version(version).unwrap() == expected,
+            "{}",
+            version
+        );
+    }
+}
+
+#[test]
+fn rejects_unknown_tls_versions() {
+    for version in ["SSL3", "TLS1.4", "TLS2", ""] {
+        assert!(parse_tls_version(version).is_err(), "{}", version);
+    }
+}
+
+fn certificate_options(
+    client_certificate_mode: Option<&str>,
+) -> serde_json::Result<CertificateOptions> {
+    let mut options = serde_json::json!({
+        "CertType": "PemFile",
+        "FilePath": "cert.pem",
+        "KeyFilePath": "key.pem",
+    });
+    if let Some(mode) = client_certificate_mode {
+        options["ClientCertificateMode"] = mode.into();
+    }
+    serde_json::from_value(options)
+}
+
+#[test]
+fn parses_client_certificate_modes() {
+    for (mode, expected) in [
+        ("None", ClientCertificateMode::None),
+        ("Optional", ClientCertificateMode::Optional),
+        ("Require", ClientCertificateMode::Require),
+    ] {
+        assert_eq!(
+            certificate_options(Some(mode))
+                .unwrap()
+                .client_certificate_mode,
+            expected
+        );
+    }
+}
+
+#[test]
+fn client_certificates_are_not_requested_by_default() {
+    assert_eq!(
+        certificate_options(None).unwrap().client_certificate_mode,
+        ClientCertificateMode::None
+    );
+}
+
+#[test]
+fn rejects_unknown_client_certificate_mode() {
+    assert!(certificate_options(Some("Sometimes")).is_err());
+    assert!(certificate_options(Some("require")).is_err());
+}
@@ -0,0 +1,396 @@
+use std::io::Cursor;
+
+use bson::{rawdoc, RawDocumentBuf};
+use documentdb_gateway::{
+    bson::read_document_bytes,
+    error::{ErrorCode, Result},
+    protocol::{
+        message::Message, opcode::OpCode, reader, MAX_BSON_INTERNAL_SIZE, MAX_MESSAGE_SIZE_BYTES,
+    },
+    requests::{Request, RequestMessage, RequestType},
+};
+
+fn document() -> Vec<u8> {
+    rawdoc! { "ping": 1, "$db": "admin" }.into_bytes()
+}
+
+fn assert_error_code<T: std::fmt::Debug>(result: Result<T>, expected: ErrorCode) {
+    let code = result.unwrap_err().error_code_enum();
+    assert_eq!(code.map(|c| c as i32), Some(expected as i32));
+}
+
+fn header(length: i32) -> Vec<u8> {
+    let mut bytes = length.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&[0; 12]);
+    bytes
+}
+
+fn read_op_msg(body: &[u8]) -> Result<Message<'_>> {
+    Message::read_from_op_msg(Cursor::new(body), 1, 0)
+}
+
+#[tokio::test]
+async fn rejects_negative_message_length() {
+    let bytes = header(-1);
+    assert_error_code(
+        reader::read_header(&mut bytes.as_slice()).await,
+        ErrorCode::ProtocolError,
+    );
+}
+
+#[tokio::test]
+async fn rejects_message_length_shorter_than_the_header() {
+    let bytes = header(15);
+    assert_error_code(
+        reader::read_header(&mut bytes.as_slice()).await,
+        ErrorCode::ProtocolError,
+    );
+}
+
+#[tokio::test]
+async fn rejects_oversized_message_length() {
+    let bytes = header(MAX_MESSAGE_SIZE_BYTES + 1);
+    assert_error_code(
+        reader::read_header(&mut bytes.as_slice()).await,
+        ErrorCode::ProtocolError,
+    );
+}
+
+#[test]
+fn rejects_negative_document_length() {
+    let mut bytes = document();
+    bytes[0..4].copy_from_slice(&(-1_i32).to_le_bytes());
+    assert_error_code(
+        read_document_bytes(&mut Cursor::new(bytes.as_slice())),
+        ErrorCode::ProtocolError,
+    );
+}
+
+#[test]
+fn rejects_oversized_document_length() {
+    let mut bytes = document();
+    bytes[0..4].copy_from_slice(&(MAX_BSON_INTERNAL_SIZE + 1).to_le_bytes());
+    assert_error_code(
+        read_document_bytes(&mut Cursor::new(bytes.as_slice())),
+        ErrorCode::BSONObjectTooLarge,
+    );
+}
+
+#[test]
+fn rejects_truncated_document() {
+    let bytes = document();
+    assert_error_code(
+        read_document_bytes(&mut Cursor::new(&bytes[..bytes.len() - 1])),
+        ErrorCode::ProtocolError,
+    );
+}
+
+#[test]
+fn rejects_truncated_document_length() {
+    assert_error_code(
+        read_document_bytes(&mut Cursor::new([5_u8, 0].as_slice())),
+        ErrorCode::ProtocolError,
+    );
+}
+
+#[test]
+fn rejects_truncated_flags() {
+    assert_error_code(read_op_msg(&[0, 0]), ErrorCode::ProtocolError);
+}
+
+#[test]
+fn rejects_checksum_without_room() {
+    assert_error_code(read_op_msg(&1_u32.to_le_bytes()), ErrorCode::ProtocolError);
+}
+
+#[test]
+fn rejects_truncated_document_section() {
+    let mut body = vec![0, 0, 0, 0, 0];
+    body.extend_from_slice(&document());
+    body.pop();
+    assert_error_code(read_op_msg(&body), ErrorCode::ProtocolError);
+}
+
+#[test]
+fn rejects_unknown_section_kind() {
+    let mut body = vec![0, 0, 0, 0, 2];
+    body.extend_from_slice(&document());
+    assert_error_code(read_op_msg(&body), ErrorCode::ProtocolError);
+}
+
+// Builds an OP_MSG body with CHECKSUM_PRESENT set, ending with the CRC-32C of the whole message
+fn checksummed_body() -> Vec<u8> {
+    let mut body = vec![1, 0, 0, 0, 0];
+    body.extend_from_slice(&document());
+
+    let length = (16 + body.len() + 4) as i32;
+    let mut message = length.to_le_bytes().to_vec();
+    message.extend_from_slice(&1_i32.to_le_bytes());
+    message.extend_from_slice(&0_i32.to_le_bytes());
+    message.extend_from_slice(&(OpCode::Msg as i32).to_le_bytes());
+    message.extend_from_slice(&body);
+
+    body.extend_from_slice(&crc32c::crc32c(&message).to_le_bytes());
+    body
+}
+
+#[test]
+fn accepts_valid_checksum() {
+    read_op_msg(&checksummed_body()).unwrap();
+}
+
+#[test]
+fn rejects_corrupted_message_with_checksum() {
+    let mut body = checksummed_body();
+    // Flip a byte inside the command document
+    body[10] ^= 1;
+    assert_error_code(read_op_msg(&body), ErrorCode::ChecksumMismatch);
+
+    let mut body = checksummed_body();
+    let last = body.len() - 1;
+    body[last] ^= 1;
+    assert_error_code(read_op_msg(&body), ErrorCode::ChecksumMismatch);
+}
+
+// Builds an OP_MSG body holding a kind-0 section and a kind-1 section with the given size
+fn sequence_body(size: i32) -> Vec<u8> {
+    let mut body = vec![0, 0, 0, 0, 0];
+    body.extend_from_slice(&document());
+    body.push(1);
+    body.extend_from_slice(&size.to_le_bytes());
+    body.extend_from_slice(b"documents\0");
+    body.extend_from_slice(&document());
+    body
+}
+
+#[test]
+fn accepts_valid_sequence_section() {
+    let size = (4 + "documents\0".len() + document().len()) as i32;
+    read_op_msg(&sequence_body(size)).unwrap();
+}
+
+#[test]
+fn rejects_negative_sequence_size() {
+    assert_error_code(read_op_msg(&sequence_body(-1)), ErrorCode::ProtocolError);
+}
+
+#[test]
+fn rejects_sequence_size_past_the_end() {
+    let size = (4 + "documents\0".len() + document().len()) as i32 + 1;
+    assert_error_code(read_op_msg(&sequence_body(size)), ErrorCode::ProtocolError);
+}
+
+#[test]
+fn rejects_truncated_sequence_size() {
+    let mut body = vec![0, 0, 0, 0, 0];
+    body.extend_from_slice(&document());
+    body.extend_from_slice(&[1, 8, 0]);
+    assert_error_code(read_op_msg(&body), ErrorCode::ProtocolError);
+}
+
+#[tokio::test]
+async fn rejects_oversized_query_document() {
+    let mut body = 0_u32.to_le_bytes().to_vec();
+    body.extend_from_slice(b"db.coll\0");
+    body.extend_from_slice(&0_i32.to_le_bytes());
+    body.extend_from_slice(&0_i32.to_le_bytes());
+    let mut query = document();
+    query[0..4].copy_from_slice(&(MAX_BSON_INTERNAL_SIZE + 1).to_le_bytes());
+    body.extend_from_slice(&query);
+
+    assert_error_code(
+        reader::parse_query(&body).await.map(|(_, legacy)| legacy),
+        ErrorCode::BSONObjectTooLarge,
+    );
+}
+
+fn legacy_message(op_code: OpCode, body: Vec<u8>) -> RequestMessage {
+    RequestMessage {
+        request: body,
+        op_code,
+        request_id: 1,
+        response_to: 0,
+    }
+}
+
+// Builds the start of a legacy message body: a leading int32 followed by the namespace
+fn namespaced_body(leading: i32) -> Vec<u8> {
+    let mut body = leading.to_le_bytes().to_vec();
+    body.extend_from_slice(b"db.coll\0");
+    body
+}
+
+fn assert_request(request: Request<'_>, request_type: RequestType, expected: RawDocumentBuf) {
+    assert_eq!(request.request_type(), &request_type);
+    assert_eq!(request.document().to_raw_document_buf(), expected);
+}
+
+#[tokio::test]
+async fn translates_op_update() {
+    let mut body = namespaced_body(0);
+    // Upsert
+    body.extend_from_slice(&1_u32.to_le_bytes());
+    body.extend_from_slice(rawdoc! { "a": 1 }.as_bytes());
+    body.extend_from_slice(rawdoc! { "$set": { "b": 2 } }.as_bytes());
+    let message = legacy_message(OpCode::Update, body);
+
+    assert_request(
+        reader::parse_update(&message).await.unwrap(),
+        RequestType::Update,
+        rawdoc! {
+            "update": "coll",
+            "updates": [{ "q": { "a": 1 }, "u": { "$set": { "b": 2 } }, "upsert": true, "multi": false }],
+            "ordered": true,
+            "$db": "db",
+        },
+    );
+}
+
+#[tokio::test]
+async fn translates_op_delete() {
+    for (flags, limit) in [(0_u32, 0), (1, 1)] {
+        let mut body = namespaced_body(0);
+        body.extend_from_slice(&flags.to_le_bytes());
+        body.extend_from_slice(rawdoc! { "a": 1 }.as_bytes());
+        let message = legacy_message(OpCode::Delete, body);
+
+        assert_request(
+            reader::parse_delete(&message).await.unwrap(),
+            RequestType::Delete,
+            rawdoc! {
+                "delete": "coll",
+                "deletes": [{ "q": { "a": 1 }, "limit": limit }],
+                "ordered": true,
+                "$db": "db",
+            },
+        );
+    }
+}
+
+#[tokio::test]
+async fn translates_op_get_more() {
+    let get_more = |number_to_return: i32| {
+        let mut body = namespaced_body(0);
+        body.extend_from_slice(&number_to_return.to_le_bytes());
+        body.extend_from_slice(&42_i64.to_le_bytes());
+        legacy_message(OpCode::GetMore, body)
+    };
+
+    assert_request(
+        reader::parse_get_more(&get_more(5)).await.unwrap(),
+        RequestType::GetMore,
+        rawdoc! { "getMore": 42_i64, "collection": "coll", "batchSize": 5_i64, "$db": "db" },
+    );
+
+    // Without a number to return the batch size is left to the server
+    assert_request(
+        reader::parse_get_more(&get_more(0)).await.unwrap(),
+        RequestType::GetMore,
+        rawdoc! { "getMore": 42_i64, "collection": "coll", "$db": "db" },
+    );
+}
+
+#[tokio::test]
+async fn translates_op_kill_cursors() {
+    let kill_cursors = |number_of_cursor_ids: i32, cursor_ids: &[i64]| {
+        let mut body = 0_i32.to_le_bytes().to_vec();
+        body.extend_from_slice(&number_of_cursor_ids.to_le_bytes());
+        for cursor_id in cursor_ids {
+            body.extend_from_slice(&cursor_id.to_le_bytes());
+        }
+        legacy_message(OpCode::KillCursors, body)
+    };
+
+    assert_request(
+        reader::parse_kill_cursors(&kill_cursors(2, &[7, 8]))
+            .await
+            .unwrap(),
+        RequestType::KillCursors,
+        rawdoc! { "killCursors": "", "cursors": [7_i64, 8_i64], "$db": "admin" },
+    );
+
+    assert_error_code(
+        reader::parse_kill_cursors(&kill_cursors(3, &[7, 8])).await,
+        ErrorCode::ProtocolError,
+    );
+}
+
+// Builds an OP_QUERY body against db.coll
+fn query_body(
+    flags: u32,
+    number_to_skip: i32,
+    number_to_return: i32,
+    query: RawDocumentBuf,
+) -> Vec<u8> {
+    let mut body = namespaced_body(flags as i32);
+    body.extend_from_slice(&number_to_skip.to_le_bytes());
+    body.extend_from_slice(&number_to_return.to_le_bytes());
+    body.extend_from_slice(query.as_bytes());
+    body
+}
+
+async fn assert_legacy_find(body: Vec<u8>, expected: RawDocumentBuf) {
+    let (request, legacy_cursor_reply) = reader::parse_query(&body).await.unwrap();
+    assert!(legacy_cursor_reply);
+    assert_request(request, RequestType::Find, expected);
+}
+
+#[tokio::test]
+async fn translates_op_query_to_find() {
+    // $query wraps the filter alongside modifiers
+    let mut body = query_body(
+        0,
+        2,
+        10,
+        rawdoc! { "$query": { "a": 1 }, "$orderby": { "b": -1 }, "$comment": "legacy" },
+    );
+    body.extend_from_slice(rawdoc! { "a": 1 }.as_bytes());
+    assert_legacy_find(
+        body,
+        rawdoc! {
+            "find": "coll",
+            "filter": { "a": 1 },
+            "sort": { "b": -1 },
+            "comment": "legacy",
+            "projection": { "a": 1 },
+            "skip": 2_i64,
+            "batchSize": 10_i64,
+            "$db": "db",
+        },
+    )
+    .await;
+
+    // query wraps the filter when it holds a document
+    assert_legacy_find(
+        query_body(
+            0,
+            0,
+            0,
+            rawdoc! { "query": { "a": 1 }, "orderby": { "b": 1 } },
+        ),
+        rawdoc! { "find": "coll", "filter": { "a": 1 }, "sort": { "b": 1 }, "$db": "db" },
+    )
+    .await;
+
+    // Otherwise it is a filter on a field named query
+    assert_legacy_find(
+        query_body(0, 0, 0, rawdoc! { "query": "text" }),
+        rawdoc! { "find": "coll", "filter": { "query": "text" }, "$db": "db" },
+    )
+    .await;
+
+    // A negative number to return asks for a single batch, and flags map to their options
+    assert_legacy_find(
+        query_body(2 | 16, 0, -3, rawdoc! {}),
+        rawdoc! {
+            "find": "coll",
+            "filter": {},
+            "limit": 3_i64,
+            "singleBatch": true,
+            "tailable": true,
+            "noCursorTimeout": true,
+            "$db": "db",
+        },
+    )
+    .await;
+}
@@ -0,0 +1,144 @@
+use std::io::Cursor;
+
+use bson::{rawdoc, RawArrayBuf, RawDocument, RawDocumentBuf};
+use documentdb_gateway::{
+    protocol::{header::Header, opcode::OpCode},
+    responses::writer,
+};
+
+fn header(response_document_sequences: bool) -> Header {
+    Header {
+        length: 0,
+        request_id: 1,
+        response_to: 0,
+        op_code: OpCode::Msg,
+        activity_id: String::new(),
+        compressor: None,
+        response_checksum: false,
+        response_document_sequences,
+    }
+}
+
+fn batch() -> RawArrayBuf {
+    let mut batch = RawArrayBuf::new();
+    for i in 0..3 {
+        batch.push(rawdoc! { "_id": i, "a": "value" });
+    }
+    batch
+}
+
+async fn write(response_document_sequences: bool, response: &RawDocument) -> Vec<u8> {
+    let mut stream = Cursor::new(Vec::new());
+    writer::write_message(&header(response_document_sequences), response, &mut stream)
+        .await
+        .unwrap();
+    stream.into_inner()
+}
+
+// Reads the document at the start of `bytes`
+fn read_document(bytes: &[u8]) -> &RawDocument {
+    let length = i32::from_le_bytes(bytes[0..4].try_into().unwrap()) as usize;
+    RawDocument::from_bytes(&bytes[..length]).unwrap()
+}
+
+#[tokio::test]
+async fn writes_cursor_batch_as_document_sequence() {
+    for batch_key in ["firstBatch", "nextBatch"] {
+        let mut cursor = rawdoc! { "id": 5_i64 };
+        cursor.append(batch_key, batch());
+        cursor.append("ns", "db.coll");
+        let response = rawdoc! { "cursor": cursor, "ok": 1.0 };
+
+        let message = write(true, &response).await;
+        assert_eq!(
+            i32::from_le_bytes(message[0..4].try_into().unwrap()) as usize,
+            message.len()
+        );
+
+        // Flags, then the kind-0 section holding the response without its batch
+        let mut position = Header::LENGTH + 4;
+        assert_eq!(message[position], 0);
+        position += 1;
+        let command = read_document(&message[position..]);
+        assert_eq!(
+            command.to_raw_document_buf(),
+            rawdoc! { "cursor": { "id": 5_i64, "ns": "db.coll" }, "ok": 1.0 }
+        );
+        position += command.as_bytes().len();
+
+        // The kind-1 section holding the batch documents
+        assert_eq!(message[position], 1);
+        position += 1;
+        let size = i32::from_le_bytes(message[position..position + 4].try_into().unwrap());
+        assert_eq!(position + size as usize, message.len());
+        position += 4;
+        let identifier = format!("cursor.{}\0", batch_key);
+        assert_eq!(
+            &message[position..position + identifier.len()],
+            identifier.as_bytes()
+        );
+        position += identifier.len();
+
+        let mut documents: Vec<RawDocumentBuf> = Vec::new();
+        while position < message.len() {
+            let document = read_document(&message[position..]);
+            position += document.as_bytes().len();
+            documents.push(document.to_raw_document_buf());
+        }
+        let mut expected = Vec::new();
+        for document in &batch() {
+            expected.push(
+                document
+                    .unwrap()
+                    .as_document()
+                    .unwrap()
+                    .to_raw_document_buf(),
+            );
+        }
+        assert_eq!(documents, expected);
+    }
+}
+
+#[tokio::test]
+async fn writes_single_section_without_cursor_batch() {
+    let response = rawdoc! { "ok": 1.0, "n": 1 };
+    let message = write(true, &response).await;
+    assert_eq!(message[Header::LENGTH + 4], 0);
+    assert_eq!(&message[Header::LENGTH + 5..], response.as_bytes());
+}
+
+#[tokio::test]
+async fn writes_single_section_when_sequences_are_disabled() {
+    let response = rawdoc! { "cursor": { "firstBatch": batch(), "id": 0_i64 }, "ok": 1.0 };
+    let message = write(false, &response).await;
+    assert_eq!(message[Header::LENGTH + 4], 0);
+    assert_eq!(&message[Header::LENGTH + 5..], response.as_bytes());
+}
+
+#[tokio::test]
+async fn writes_verifiable_checksum_when_enabled() {
+    let response = rawdoc! { "ok": 1.0, "n": 1 };
+    let header = Header {
+        response_checksum: true,
+        ..header(false)
+    };
+    let mut stream = Cursor::new(Vec::new());
+    writer::write_message(&header, &response, &mut stream)
+        .await
+        .unwrap();
+    let message = stream.into_inner();
+
+    // CHECKSUM_PRESENT is set, and the CRC-32C of everything before it ends the message
+    let flags = u32::from_le_bytes(
+        message[Header::LENGTH..Header::LENGTH + 4]
+            .try_into()
+            .unwrap(),
+    );
+    assert_eq!(flags & 1, 1);
+    let (contents, checksum) = message.split_at(message.len() - 4);
+    assert_eq!(
+        u32::from_le_bytes(checksum.try_into().unwrap()),
+        crc32c::crc32c(contents)
+    );
+    assert_eq!(&contents[Header::LENGTH + 5..], response.as_bytes());
+}
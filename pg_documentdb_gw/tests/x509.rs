@@ -0,0 +1,60 @@
+use documentdb_gateway::auth::certificate_subject;
+use openssl::x509::{X509Name, X509NameBuilder};
+
+fn name(entries: &[(&str, &str)]) -> X509Name {
+    let mut builder = X509NameBuilder::new().unwrap();
+    for (field, value) in entries {
+        builder.append_entry_by_text(field, value).unwrap();
+    }
+    builder.build()
+}
+
+#[test]
+fn uses_full_subject_most_specific_first() {
+    let subject = name(&[
+        ("C", "US"),
+        ("O", "Contoso"),
+        ("OU", "Sales"),
+        ("CN", "svc"),
+    ]);
+    assert_eq!(
+        certificate_subject(&subject).unwrap(),
+        "CN=svc,OU=Sales,O=Contoso,C=US"
+    );
+}
+
+#[test]
+fn same_common_name_in_different_units_differs() {
+    let sales = name(&[("O", "Contoso"), ("OU", "Sales"), ("CN", "svc")]);
+    let finance = name(&[("O", "Contoso"), ("OU", "Finance"), ("CN", "svc")]);
+    assert_ne!(
+        certificate_subject(&sales).unwrap(),
+        certificate_subject(&finance).unwrap()
+    );
+}
+
+#[test]
+fn subject_without_common_name_is_accepted() {
+    let subject = name(&[("O", "Contoso"), ("OU", "Sales")]);
+    assert_eq!(certificate_subject(&subject).unwrap(), "OU=Sales,O=Contoso");
+}
+
+#[test]
+fn escapes_special_characters() {
+    let subject = name(&[("O", "Contoso, Inc."), ("CN", "a+b;c\"d\\e<f>")]);
+    assert_eq!(
+        certificate_subject(&subject).unwrap(),
+        "CN=a\\+b\\;c\\\"d\\\\e\\<f\\>,O=Contoso\\, Inc."
+    );
+
+    let subject = name(&[("CN", "#lead and trail ")]);
+    assert_eq!(
+        certificate_subject(&subject).unwrap(),
+        "CN=\\#lead and trail\\ "
+    );
+}
+
+#[test]
+fn rejects_empty_subject() {
+    assert!(certificate_subject(&name(&[])).is_err());
+}